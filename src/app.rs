@@ -1,59 +1,172 @@
-use std::io::Stdout;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs;
 use std::path;
-use std::path::PathBuf;
-
-use tui::backend::TermionBackend;
-use tui::Terminal;
-
-use termion::raw::RawTerminal;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::commands;
+use crate::config;
+use crate::config::{Config, Operation};
+use crate::error::Error;
+use crate::events::Waker;
 use crate::file_ops;
 use crate::file_ops::DirectoryItem;
+use crate::finder::Finder;
+use crate::grep::Grep;
+use crate::history::{History, HistorySearch};
+use crate::jobs::{JobKind, JobManager, JobResult, Progress};
+use crate::journal;
+use crate::journal::Journal;
+use crate::line_editor::LineEditor;
+use crate::messages::Messages;
+use crate::opener;
+use crate::paste::{PastePlan, Resolution};
+use crate::pattern::Pattern;
+use crate::preview::Previewer;
+use crate::shell;
+use crate::sort::SortOrder;
+use crate::trash::{self, Trash};
+use crate::ui;
+use crate::watch::{self, ChangeKind, Watcher};
 
-pub struct App<'a> {
+pub struct App {
     pub current_directory: path::PathBuf,
-    pub terminal: &'a mut Terminal<TermionBackend<RawTerminal<Stdout>>>,
+    //Rows in the terminal, which the listing's height is worked out from
+    pub terminal_height: u16,
     pub mode: Mode,
     pub selection_index: Option<usize>,
     pub directory_contents: Vec<DirectoryItem>,
-    pub command_buffer: Vec<char>,
-    pub error: Option<String>,
-    pub buffered_file_name: Option<String>,
+    //Everything in the directory, before hidden files, ignored files and the filter are removed
+    pub all_contents: Vec<DirectoryItem>,
+    pub show_hidden: bool,
+    pub respect_ignore_files: bool,
+    pub ignored_files: HashSet<PathBuf>,
+    pub filter: Option<Pattern>,
+    //The line typed after ':', and the commands typed before it
+    pub command_line: LineEditor,
+    pub history: History,
+    //Set while looking back through the history with Ctrl-r
+    pub history_search: Option<HistorySearch>,
+    //What's typed after '/'
+    pub search_buffer: String,
+    //The last search, kept after leaving search mode for n/N and highlighting
+    pub search: Option<Pattern>,
+    //Where the selection was when the search started, to go back to if it's cancelled
+    search_origin: Option<usize>,
+    //What's been reported this session, the newest shown until it expires
+    pub messages: Messages,
+    pub marked_files: HashSet<PathBuf>,
+    pub selection_anchor: Option<PathBuf>,
+    pub clipboard: Option<Clipboard>,
+    pub paste_plan: Option<PastePlan>,
+    pub jobs: JobManager,
+    //Where deleted files go. Set by main, so the app starts without one and tests don't use the
+    //user's trash.
+    pub trash: Option<Trash>,
+    pub trash_info: HashMap<OsString, trash::TrashEntry>,
+    pub confirmation: Option<Confirmation>,
+    pub config: Config,
+    pub sort: SortOrder,
+    pub journal: Journal,
+    pub text_view: Option<TextView>,
+    pub finder: Option<Finder>,
+    //The last :grep, kept after its view is closed so it can be opened again
+    pub grep: Option<Grep>,
+    //A program to hand the terminal to, such as an editor; the main loop runs it
+    pub foreground_command: Option<Command>,
+    //Output of commands run in the background with :!cmd &
+    pub shell_log: Arc<Mutex<shell::Log>>,
+    pub show_preview: bool,
+    pub previewer: Previewer,
     pub window_height: u16,
+    //Reports changes to the listed and previewed directories, if inotify is available
+    watcher: Option<Watcher>,
+    //Whether the listed directory's changes are being reported, or it has to be read again now
+    //and then
+    watching: bool,
+    last_read: Instant,
+    //Wakes the main loop from other threads, e.g. when a confirmation has been measured
+    waker: Waker,
 
-    file_buffer: Option<Vec<u8>>,
     max_file_selection: usize,
 }
 
-impl<'a> App<'a> {
-    pub fn new(terminal: &'a mut Terminal<TermionBackend<RawTerminal<Stdout>>>) -> App<'a> {
-        let current_dir = path::PathBuf::from("/");
-        let window_height = terminal.size().unwrap().height - 5; //borders + command window height add up to 5
+//How often the screen is refreshed while jobs, the finder or grep are working
+const BUSY_INTERVAL: Duration = Duration::from_millis(100);
+//How often the directory is read again when it can't be watched for changes
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
 
+impl App {
+    pub fn new(
+        directory: PathBuf,
+        select: Option<OsString>,
+        config: Config,
+        waker: Waker,
+        terminal_height: u16,
+    ) -> Result<App, std::io::Error> {
         let mut app = App {
-            current_directory: current_dir,
-            terminal,
+            current_directory: directory,
+            terminal_height,
             mode: Mode::Browse,
             selection_index: Some(0),
             max_file_selection: 0,
             directory_contents: Vec::new(),
-            command_buffer: Vec::new(),
-            file_buffer: None,
-            buffered_file_name: None,
-            error: None,
-            window_height: window_height,
-        };
-
-        if let Err(error) = app.populate_files() {
-            panic!(format!(
-                "Error opening {:?}: {:?}",
-                app.current_directory,
-                error.kind()
-            ));
+            all_contents: Vec::new(),
+            show_hidden: config.show_hidden,
+            show_preview: config.preview,
+            previewer: Previewer::new(config.theme.clone(), waker.clone()),
+            respect_ignore_files: config.respect_ignore_files,
+            ignored_files: HashSet::new(),
+            filter: None,
+            command_line: LineEditor::default(),
+            history: History::default(),
+            history_search: None,
+            search_buffer: String::new(),
+            search: None,
+            search_origin: None,
+            messages: Messages::default(),
+            marked_files: HashSet::new(),
+            selection_anchor: None,
+            clipboard: None,
+            paste_plan: None,
+            jobs: JobManager::new(waker.clone()),
+            shell_log: Arc::default(),
+            trash: None,
+            trash_info: HashMap::new(),
+            confirmation: None,
+            sort: config.sort,
+            config,
+            journal: Journal::default(),
+            text_view: None,
+            finder: None,
+            grep: None,
+            foreground_command: None,
+            watcher: Watcher::new(waker.clone()).ok(),
+            waker,
+            watching: false,
+            last_read: Instant::now(),
+            window_height: 0,
+        };
+
+        app.update_window_height();
+
+        app.populate_files()?;
+
+        if let Some(name) = select {
+            if !app.select_file_named(&name) {
+                app.messages.warn(format!(
+                    "No entry named '{}'",
+                    file_ops::display_name(&name)
+                ));
+            }
         }
 
-        app
+        Ok(app)
     }
 
     pub fn move_selection_down(&mut self) {
@@ -84,7 +197,7 @@ impl<'a> App<'a> {
 
     pub fn move_selection_right(&mut self) {
         if let Some(selection_index) = self.selection_index {
-            if selection_index + self.window_height as usize <= self.directory_contents.len() - 1 {
+            if selection_index + (self.window_height as usize) < self.directory_contents.len() {
                 self.selection_index = Some(selection_index + self.window_height as usize);
             } else {
                 self.selection_index = Some(self.directory_contents.len() - 1);
@@ -93,22 +206,195 @@ impl<'a> App<'a> {
     }
 
     pub fn update_window_height(&mut self) {
-        self.window_height = self.terminal.size().unwrap().height - 5; //borders + command window height add up to 5
+        //borders + command window height add up to 5, plus the jobs panel while jobs are running
+        self.window_height = self
+            .terminal_height
+            .saturating_sub(5 + ui::jobs_panel_height(&self.jobs.jobs));
+    }
+
+    pub fn set_terminal_height(&mut self, height: u16) {
+        self.terminal_height = height;
+        self.update_window_height();
     }
 
     pub fn populate_files(&mut self) -> Result<(), std::io::Error> {
-        let mut files = file_ops::get_files_for_current_directory(&self)?;
+        //Watched first, so nothing that changes while reading is missed
+        let directory = &self.current_directory;
+        self.watching = self
+            .watcher
+            .as_mut()
+            .is_some_and(|watcher| watcher.watch_listed(directory));
+        self.last_read = Instant::now();
+        self.all_contents = file_ops::get_files_for_current_directory(self)?;
+
+        self.ignored_files = if self.respect_ignore_files {
+            file_ops::get_ignored_files(&self.current_directory, &self.all_contents)
+        } else {
+            HashSet::new()
+        };
+
+        //Original locations of trashed files, shown when browsing the trash
+        if let Some(trash) = self.browsed_trash() {
+            self.trash_info = trash
+                .list()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| (entry.name.clone(), entry))
+                .collect();
+        } else if !self.trash_info.is_empty() {
+            self.trash_info.clear();
+        }
+
+        self.apply_filters();
+        Ok(())
+    }
 
-        files.sort();
+    //Rebuilds the visible listing from the loaded entries without reading the disk again,
+    //keeping the same entry selected if it's still shown
+    pub fn apply_filters(&mut self) {
+        //The index may be past the end of the old listing if it was just re-read
+        let selected = self
+            .selection_index
+            .and_then(|index| self.directory_contents.get(index))
+            .map(|item| item.path().to_path_buf());
 
+        let live_filter = self.get_live_filter();
+        let filter = live_filter.as_ref().or(self.filter.as_ref());
+        let mut files: Vec<DirectoryItem> = self
+            .all_contents
+            .iter()
+            .filter(|item| self.show_hidden || !item.is_hidden())
+            .filter(|item| !self.ignored_files.contains(item.path()))
+            .filter(|item| filter.is_none_or(|filter| filter.is_match(item.name())))
+            .cloned()
+            .collect();
+        self.sort.sort(&mut files);
         self.directory_contents = files;
+
         self.max_file_selection = self.directory_contents.len();
 
+        let selected_index = selected.and_then(|selected| {
+            self.directory_contents
+                .iter()
+                .position(|item| item.path() == selected)
+        });
         if self.max_file_selection == 0 {
             self.selection_index = None;
+        } else if selected_index.is_some() {
+            self.selection_index = selected_index;
+        } else {
+            //Keep the selection inside the list when entries disappear or appear
+            let last = self.max_file_selection - 1;
+            self.selection_index = Some(self.selection_index.map_or(0, |index| index.min(last)));
         }
 
-        Ok(())
+        //Marks are kept by path, so drop the ones whose entries no longer exist
+        let directory_contents = &self.directory_contents;
+        self.marked_files
+            .retain(|path| directory_contents.iter().any(|item| item.path() == path));
+
+        if let Some(anchor) = &self.selection_anchor {
+            if !self
+                .directory_contents
+                .iter()
+                .any(|item| item.path() == anchor)
+            {
+                self.selection_anchor = None;
+            }
+        }
+    }
+
+    //The filter being typed into the command box, so the listing narrows as you type
+    fn get_live_filter(&self) -> Option<Pattern> {
+        if self.mode != Mode::Command {
+            return None;
+        }
+
+        let command = self.command_line.text();
+        let (name, text) = command.split_once(' ')?;
+        let text = text.trim();
+        if !name.eq_ignore_ascii_case("filter") || text.is_empty() {
+            None
+        } else {
+            Pattern::parse(text).ok()
+        }
+    }
+
+    //`:filter [pattern]`; with no pattern the filter is removed
+    pub fn set_filter(&mut self, arguments: &[String]) {
+        if arguments.is_empty() {
+            self.filter = None;
+        } else {
+            match Pattern::parse(&arguments.join(" ")) {
+                Ok(pattern) => self.filter = Some(pattern),
+                Err(err) => self.messages.report(Error::InvalidPattern(err)),
+            }
+        }
+        self.apply_filters();
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.apply_filters();
+    }
+
+    pub fn toggle_ignore_files(&mut self) {
+        self.respect_ignore_files = !self.respect_ignore_files;
+        if let Err(err) = self.populate_files() {
+            self.messages
+                .report(Error::io("read", &self.current_directory, err));
+        }
+    }
+
+    pub fn select_file_named(&mut self, name: &OsStr) -> bool {
+        let index = self
+            .directory_contents
+            .iter()
+            .position(|item| item.name() == name);
+
+        if index.is_some() {
+            self.selection_index = index;
+        }
+
+        index.is_some()
+    }
+
+    pub fn cycle_sort_key(&mut self) {
+        self.sort.key = self.sort.key.next();
+        self.resort();
+    }
+
+    pub fn reverse_sort(&mut self) {
+        self.sort.descending = !self.sort.descending;
+        self.resort();
+    }
+
+    pub fn toggle_directories_first(&mut self) {
+        self.sort.directories_first = !self.sort.directories_first;
+        self.resort();
+    }
+
+    //`:sort [key] [asc|desc]`; with no arguments the current order is shown
+    pub fn set_sort(&mut self, arguments: &[String]) {
+        if arguments.is_empty() {
+            self.messages
+                .info(format!("Sorted by {}", self.sort.describe()));
+            return;
+        }
+
+        let mut sort = self.sort;
+        match config::parse_sort(&arguments.join(" "), &mut sort) {
+            Ok(_) => {
+                self.sort = sort;
+                self.resort();
+            }
+            Err(err) => self.messages.warn(err),
+        }
+    }
+
+    //Sorts the listing again, keeping the same entry selected
+    fn resort(&mut self) {
+        self.apply_filters();
     }
 
     pub fn change_mode(&mut self, mode: Mode) {
@@ -117,94 +403,1238 @@ impl<'a> App<'a> {
 
     pub fn open_folder(&mut self) {
         if let Some(selection_index) = self.selection_index {
-            if let DirectoryItem::Directory(path) = &self.directory_contents[selection_index] {
-                let previous_dir = self.current_directory.clone();
+            let item = &self.directory_contents[selection_index];
+            let path = item.path().to_path_buf();
+            if item.is_directory() {
+                self.change_directory(path);
+            } else {
+                self.open_file(&path);
+            }
+        }
+    }
+
+    //Runs the opener the config picks for `path`. Foreground programs take over the terminal
+    //once the key has been handled; background ones are started straight away.
+    pub fn open_file(&mut self, path: &Path) {
+        //Pipes and devices could block reading their type, and can't be opened like files
+        if !fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+            self.messages.warn(format!(
+                "{} isn't a file that can be opened",
+                file_ops::display_path(path)
+            ));
+            return;
+        }
+
+        let opener = opener::opener_for(path, &self.config.openers);
+        if opener.background {
+            if let Err(err) = opener::spawn_background(opener.command) {
+                self.messages.error(err);
+            }
+        } else {
+            self.foreground_command = Some(opener.command);
+        }
+    }
+
+    //Opens `directory`, staying where we are and showing the error if it can't be read
+    pub fn change_directory(&mut self, directory: PathBuf) -> bool {
+        let previous_dir = std::mem::replace(&mut self.current_directory, directory);
+
+        if let Err(err) = self.populate_files() {
+            let directory = std::mem::replace(&mut self.current_directory, previous_dir);
+            self.messages.report(Error::io("open", directory, err));
+            false
+        } else {
+            self.selection_index = Some(0);
+            self.clear_marks();
+            true
+        }
+    }
 
-                self.current_directory = PathBuf::from(path);
+    //Stays put, reporting why, if the parent can't be read
+    pub fn move_up_directory(&mut self) {
+        if let Some(parent) = self.current_directory.parent() {
+            self.change_directory(parent.to_path_buf());
+        }
+    }
 
-                if let Err(err) = self.populate_files() {
-                    self.current_directory = previous_dir;
-                    self.error = Some(err.to_string());
+    pub fn start_command(&mut self) {
+        self.command_line.clear();
+        self.history.reset();
+        self.history_search = None;
+        self.change_mode(Mode::Command);
+    }
+
+    pub fn cancel_command(&mut self) {
+        self.command_line.clear();
+        self.history.reset();
+        self.history_search = None;
+        self.change_mode(Mode::Browse);
+        //The live filter is dropped with the line
+        self.apply_filters();
+    }
+
+    pub fn execute_command(&mut self) {
+        let command_string = self.command_line.text();
+        self.command_line.clear();
+        if let Err(err) = self.history.add(&command_string) {
+            self.messages
+                .warn(format!("Couldn't save the command history: {}", err));
+        }
+
+        //Set before running the command, as some commands switch to another mode
+        self.change_mode(Mode::Browse);
+        commands::process_command(command_string, self);
+    }
+
+    //Up: the command before the one shown
+    pub fn previous_command(&mut self) {
+        let current = self.command_line.text();
+        if let Some(previous) = self.history.previous(&current) {
+            self.command_line.set_text(previous);
+        }
+    }
+
+    //Down: the command after the one shown, then back to what was being typed
+    pub fn next_command(&mut self) {
+        if let Some(next) = self.history.next() {
+            self.command_line.set_text(&next);
+        }
+    }
+
+    pub fn start_history_search(&mut self) {
+        self.history_search = Some(HistorySearch::new());
+    }
+
+    pub fn add_to_history_search(&mut self, character: char) {
+        if let Some(search) = &mut self.history_search {
+            search.query.push(character);
+            search.update(&self.history);
+        }
+    }
+
+    pub fn history_search_backspace(&mut self) {
+        if let Some(search) = &mut self.history_search {
+            search.query.pop();
+            search.update(&self.history);
+        }
+    }
+
+    pub fn search_history_older(&mut self) {
+        if let Some(search) = &mut self.history_search {
+            search.older(&self.history);
+        }
+    }
+
+    //Puts the command found into the command line to be run or edited
+    pub fn accept_history_search(&mut self) {
+        if let Some(search) = self.history_search.take() {
+            if let Some(command) = search.matched(&self.history) {
+                self.command_line.set_text(command);
+            }
+        }
+    }
+
+    //Leaves the line as it was before Ctrl-r
+    pub fn cancel_history_search(&mut self) {
+        self.history_search = None;
+    }
+
+    pub fn get_selected_file_path(&self) -> Option<PathBuf> {
+        self.selection_index
+            .and_then(|selection_index| self.directory_contents.get(selection_index))
+            .map(|item| item.path().to_path_buf())
+    }
+
+    pub fn start_search(&mut self) {
+        self.search_origin = self.selection_index;
+        self.search_buffer = String::new();
+        self.change_mode(Mode::Search);
+    }
+
+    pub fn add_to_search(&mut self, character: char) {
+        self.search_buffer.push(character);
+        self.update_search();
+    }
+
+    //Backspace with nothing typed leaves the search, as it does the command box
+    pub fn search_backspace(&mut self) {
+        if self.search_buffer.pop().is_some() {
+            self.update_search();
+        } else {
+            self.cancel_search();
+        }
+    }
+
+    //Highlights what the search matches so far and jumps to the first match from where it
+    //started, or back to the start if nothing matches
+    fn update_search(&mut self) {
+        let text = self.search_buffer.as_str();
+        //An unfinished regex or glob just doesn't match anything until it's valid
+        self.search = if text.is_empty() {
+            None
+        } else {
+            Pattern::parse(text).ok()
+        };
+
+        let origin = self.search_origin.unwrap_or(0);
+        self.selection_index = self.find_match(origin, true).or(self.search_origin);
+    }
+
+    pub fn accept_search(&mut self) {
+        self.change_mode(Mode::Browse);
+        if let Some(search) = &self.search {
+            if !(0..self.directory_contents.len()).any(|index| self.is_search_match(index)) {
+                self.messages
+                    .warn(format!("Pattern not found: {}", search.text));
+            }
+        }
+    }
+
+    pub fn cancel_search(&mut self) {
+        self.change_mode(Mode::Browse);
+        self.search = None;
+        self.selection_index = self.search_origin;
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+    }
+
+    //Moves to the next match after the selection (or the previous one), wrapping around
+    pub fn jump_to_match(&mut self, forward: bool) {
+        let search = match &self.search {
+            Some(search) => search,
+            None => {
+                self.messages.warn("No previous search");
+                return;
+            }
+        };
+
+        let count = self.directory_contents.len();
+        let start = match self.selection_index {
+            Some(index) if forward => (index + 1) % count,
+            Some(index) => (index + count - 1) % count,
+            None => 0,
+        };
+        match self.find_match(start, forward) {
+            Some(index) => self.selection_index = Some(index),
+            None => self
+                .messages
+                .warn(format!("Pattern not found: {}", search.text)),
+        }
+    }
+
+    //The first entry matching the search, looking from `start` in the given direction and
+    //wrapping around the end of the listing
+    fn find_match(&self, start: usize, forward: bool) -> Option<usize> {
+        let count = self.directory_contents.len();
+        (0..count)
+            .map(|offset| {
+                if forward {
+                    (start + offset) % count
                 } else {
-                    self.selection_index = Some(0);
+                    (start + count - offset) % count
                 }
+            })
+            .find(|index| self.is_search_match(*index))
+    }
+
+    pub fn is_search_match(&self, index: usize) -> bool {
+        self.search
+            .as_ref()
+            .is_some_and(|search| search.is_match(self.directory_contents[index].name()))
+    }
+
+    pub fn is_marked(&self, index: usize) -> bool {
+        if self
+            .marked_files
+            .contains(self.directory_contents[index].path())
+        {
+            return true;
+        }
+
+        match self.get_visual_range() {
+            Some((from, to)) => index >= from && index <= to,
+            None => false,
+        }
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(path) = self.get_selected_file_path() {
+            if !self.marked_files.remove(&path) {
+                self.marked_files.insert(path);
             }
+            self.move_selection_down();
         }
     }
 
-    pub fn move_up_directory(&mut self) -> Result<(), std::io::Error> {
-        let current_dir = self.current_directory.to_str().unwrap();
+    pub fn mark_all(&mut self) {
+        self.marked_files = self
+            .directory_contents
+            .iter()
+            .map(|item| item.path().to_path_buf())
+            .collect();
+    }
+
+    pub fn invert_marks(&mut self) {
+        self.marked_files = self
+            .directory_contents
+            .iter()
+            .map(|item| item.path().to_path_buf())
+            .filter(|path| !self.marked_files.contains(path))
+            .collect();
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked_files.clear();
+        self.selection_anchor = None;
+        if self.mode == Mode::Select {
+            self.change_mode(Mode::Browse);
+        }
+    }
+
+    pub fn start_visual_selection(&mut self) {
+        if let Some(path) = self.get_selected_file_path() {
+            self.selection_anchor = Some(path);
+            self.change_mode(Mode::Select);
+        }
+    }
 
-        if current_dir != "/" {
-            let mut prev_dir_split: Vec<&str> = current_dir.split("/").collect();
-            prev_dir_split.remove(prev_dir_split.len() - 1);
-            let mut new_dir_string = prev_dir_split.join("/");
-            if new_dir_string == "" {
-                new_dir_string.push_str("/");
+    //Adds the visual range to the marks and returns to Browse mode
+    pub fn commit_visual_selection(&mut self) {
+        if let Some((from, to)) = self.get_visual_range() {
+            for item in &self.directory_contents[from..=to] {
+                self.marked_files.insert(item.path().to_path_buf());
             }
+        }
 
-            self.current_directory = PathBuf::from(new_dir_string);
-            self.selection_index = Some(0);
-            self.populate_files()?;
+        self.cancel_visual_selection();
+    }
+
+    pub fn cancel_visual_selection(&mut self) {
+        self.selection_anchor = None;
+        if self.mode == Mode::Select {
+            self.change_mode(Mode::Browse);
         }
+    }
 
-        Ok(())
+    //Inclusive index range between the anchor and the cursor in Select mode
+    pub fn get_visual_range(&self) -> Option<(usize, usize)> {
+        if self.mode != Mode::Select {
+            return None;
+        }
+
+        let anchor = self.selection_anchor.as_ref()?;
+        let anchor_index = self
+            .directory_contents
+            .iter()
+            .position(|item| item.path() == anchor)?;
+        let selection_index = self.selection_index?;
+
+        Some((
+            anchor_index.min(selection_index),
+            anchor_index.max(selection_index),
+        ))
     }
 
-    pub fn add_to_command_buffer(&mut self, character: char) {
-        self.command_buffer.push(character);
+    //Paths an operation applies to: the marked entries, or the selected entry if nothing is marked
+    pub fn get_target_paths(&self) -> Vec<PathBuf> {
+        let marked: Vec<PathBuf> = self
+            .directory_contents
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.is_marked(*index))
+            .map(|(_, item)| item.path().to_path_buf())
+            .collect();
+
+        if !marked.is_empty() {
+            marked
+        } else {
+            self.get_selected_file_path().into_iter().collect()
+        }
     }
 
-    pub fn execute_command(&mut self) {
-        let command_string = self.get_command_buffer_as_string();
-        self.command_buffer = Vec::new();
-        commands::process_command(command_string, self);
+    pub fn copy_selected_to_clipboard(&mut self) {
+        self.set_clipboard(false);
+    }
+
+    pub fn cut_selected_to_clipboard(&mut self) {
+        self.set_clipboard(true);
+    }
+
+    fn set_clipboard(&mut self, cut: bool) {
+        self.commit_visual_selection();
+
+        let paths = self.get_target_paths();
+        self.clipboard = if paths.is_empty() {
+            None
+        } else {
+            Some(Clipboard { paths, cut })
+        };
+
+        self.clear_marks();
+    }
 
+    pub fn paste_clipboard(&mut self) {
+        if let Some(clipboard) = &self.clipboard {
+            let plan = PastePlan::new(clipboard, &self.current_directory);
+            self.paste_plan = Some(plan);
+            self.continue_paste();
+        }
+    }
+
+    pub fn resolve_conflict(&mut self, resolution: Resolution, apply_to_all: bool) {
+        if let Some(plan) = &mut self.paste_plan {
+            plan.resolve(resolution, apply_to_all);
+        }
+        self.continue_paste();
+    }
+
+    pub fn cancel_paste(&mut self) {
+        self.paste_plan = None;
         self.change_mode(Mode::Browse);
     }
 
-    pub fn get_command_buffer_as_string(&self) -> String {
-        let mut command_string = String::new();
-        for c in &self.command_buffer {
-            command_string.push(*c);
+    //Asks about the next conflict, or performs the paste once every conflict is resolved
+    fn continue_paste(&mut self) {
+        let plan = match self.paste_plan.take() {
+            Some(plan) => plan,
+            None => return,
+        };
+
+        if plan.next_conflict().is_some() {
+            self.paste_plan = Some(plan);
+            self.change_mode(Mode::Conflict);
+            return;
+        }
+
+        self.change_mode(Mode::Browse);
+
+        let sources: Vec<PathBuf> = plan.items.iter().map(|item| item.source.clone()).collect();
+        let description = format!(
+            "{} to {}",
+            describe_paths(&sources),
+            file_ops::display_path(&self.current_directory)
+        );
+        let kind = if plan.cut {
+            JobKind::Move
+        } else {
+            JobKind::Copy
+        };
+
+        //A cut can only be pasted once; the job puts back whatever it couldn't move
+        if plan.cut {
+            self.clipboard = None;
         }
 
-        command_string
+        self.jobs
+            .spawn(kind, description, move |progress| plan.execute(progress));
+    }
+
+    //Moves the targets to the trash, or deletes them for good when already browsing the trash
+    pub fn trash_selected(&mut self) {
+        if self.browsed_trash().is_some() {
+            return self.purge_selected();
+        }
+
+        let trash = match self.trash() {
+            Some(trash) => trash,
+            None => return,
+        };
+        if let Some(paths) = self.take_target_paths() {
+            self.confirm_or_run(Operation::Delete, Action::Trash(trash, paths));
+        }
+    }
+
+    //Deletes the targets permanently
+    pub fn purge_selected(&mut self) {
+        if let Some(paths) = self.take_target_paths() {
+            let action = match self.browsed_trash() {
+                Some(trash) => Action::PurgeFromTrash(trash.clone(), paths),
+                None => Action::Delete(paths),
+            };
+            self.confirm_or_run(Operation::Purge, action);
+        }
+    }
+
+    //The targets of an operation, clearing the marks. Shows an error if there are none.
+    fn take_target_paths(&mut self) -> Option<Vec<PathBuf>> {
+        self.commit_visual_selection();
+
+        let paths = self.get_target_paths();
+        self.clear_marks();
+
+        if paths.is_empty() {
+            self.messages.report(Error::NothingSelected);
+            None
+        } else {
+            Some(paths)
+        }
     }
 
-    pub fn get_selected_file_path(&self) -> Option<String> {
-        if self.selection_index != None {
-            let dir_item = self.directory_contents[self.selection_index.unwrap()].clone();
-            match dir_item {
-                DirectoryItem::Directory(path) | DirectoryItem::File((path, _)) => Some(path),
+    //Runs `action` straight away, or asks first if the config says `operation` needs confirming
+    fn confirm_or_run(&mut self, operation: Operation, action: Action) {
+        if self.config.confirms(operation) {
+            self.confirmation = Some(Confirmation::new(action, self.waker.clone()));
+            self.change_mode(Mode::Confirm);
+        } else {
+            self.run_action(action);
+        }
+    }
+
+    pub fn accept_confirmation(&mut self) {
+        self.change_mode(Mode::Browse);
+        if let Some(confirmation) = self.confirmation.take() {
+            self.run_action(confirmation.action);
+        }
+    }
+
+    pub fn reject_confirmation(&mut self) {
+        self.confirmation = None;
+        self.change_mode(Mode::Browse);
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Trash(trash, paths) => {
+                self.spawn_on_paths(JobKind::Trash, paths, move |paths, progress| {
+                    trash.trash_paths(paths, progress)
+                })
+            }
+            Action::Delete(paths) => {
+                self.spawn_on_paths(JobKind::Delete, paths, file_ops::delete_paths)
+            }
+            Action::PurgeFromTrash(trash, paths) => {
+                self.spawn_on_paths(JobKind::Delete, paths, move |paths, progress| {
+                    trash.purge_paths(paths, progress)
+                })
+            }
+            Action::EmptyTrash(trash) => {
+                self.jobs.spawn(
+                    JobKind::Delete,
+                    String::from("everything in the trash"),
+                    move |progress| trash.empty(progress),
+                );
+            }
+        }
+    }
+
+    fn spawn_on_paths<F>(&mut self, kind: JobKind, paths: Vec<PathBuf>, work: F)
+    where
+        F: FnOnce(Vec<PathBuf>, Arc<Progress>) -> JobResult + Send + 'static,
+    {
+        let description = describe_paths(&paths);
+        self.jobs
+            .spawn(kind, description, move |progress| work(paths, progress));
+    }
+
+    //The trash, or None after saying there isn't one
+    fn trash(&mut self) -> Option<Trash> {
+        if self.trash.is_none() {
+            self.messages.report(Error::Other(String::from(
+                "There is no trash: neither XDG_DATA_HOME nor HOME is set",
+            )));
+        }
+        self.trash.clone()
+    }
+
+    //The trash, if its files are what's being browsed
+    fn browsed_trash(&self) -> Option<&Trash> {
+        self.trash
+            .as_ref()
+            .filter(|trash| trash.is_files_directory(&self.current_directory))
+    }
+
+    pub fn open_trash(&mut self) {
+        let files = match self.trash() {
+            Some(trash) => trash.files_directory(),
+            None => return,
+        };
+        match fs::create_dir_all(&files) {
+            Ok(_) => {
+                self.change_directory(files);
+            }
+            Err(err) => self
+                .messages
+                .report(Error::Other(format!("Couldn't open the trash: {}", err))),
+        }
+    }
+
+    //Puts the targets back where they were deleted from
+    pub fn restore_selected(&mut self) {
+        let trash = match self.browsed_trash() {
+            Some(trash) => trash.clone(),
+            None => {
+                self.messages
+                    .warn("Open the trash with :trash to restore files");
+                return;
+            }
+        };
+
+        self.commit_visual_selection();
+        let mut errors: Vec<String> = Vec::new();
+        for path in self.get_target_paths() {
+            let name = path.file_name().unwrap_or_default();
+            if let Err(err) = trash.restore(name) {
+                errors.push(Error::io("restore", name, err).to_string());
+            }
+        }
+
+        if !errors.is_empty() {
+            self.messages.error(errors.join(", "));
+        }
+        self.clear_marks();
+    }
+
+    pub fn empty_trash(&mut self) {
+        if let Some(trash) = self.trash() {
+            self.confirm_or_run(Operation::EmptyTrash, Action::EmptyTrash(trash));
+        }
+    }
+
+    //Takes in finished jobs. True while jobs are running, as their progress keeps changing.
+    pub fn update_jobs(&mut self) -> Result<bool, std::io::Error> {
+        let finished = self.jobs.take_finished();
+        let any_finished = !finished.is_empty();
+        for (job, result) in finished {
+            if job.progress.is_cancelled() {
+                self.messages
+                    .warn(format!("{} #{} cancelled", job.kind.name(), job.id));
+            } else if !result.errors.is_empty() {
+                self.messages.error(format!(
+                    "{} #{} finished with {} error(s): {}",
+                    job.kind.name(),
+                    job.id,
+                    result.errors.len(),
+                    result
+                        .errors
+                        .iter()
+                        .map(Error::to_string)
+                        .collect::<Vec<String>>()
+                        .join("; ")
+                ));
+            }
+
+            let steps: Vec<journal::Step> = result
+                .done
+                .into_iter()
+                .map(|(from, to)| journal::Step::new(from, to))
+                .collect();
+            let kind = match job.kind {
+                JobKind::Move => Some(journal::Kind::Move),
+                JobKind::Trash => Some(journal::Kind::Trash),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.journal.record(journal::Operation::new(kind, steps));
+            }
+
+            if job.kind == JobKind::Move && !result.failed.is_empty() && self.clipboard.is_none() {
+                self.clipboard = Some(Clipboard {
+                    paths: result.failed,
+                    cut: true,
+                });
+            }
+        }
+
+        if any_finished {
+            self.refresh()?;
+        }
+        Ok(any_finished || !self.jobs.jobs.is_empty())
+    }
+
+    pub fn undo(&mut self) {
+        match self.journal.undo() {
+            Ok(done) => self.messages.info(done),
+            Err(err) => self.messages.report(err),
+        }
+    }
+
+    pub fn redo(&mut self) {
+        match self.journal.redo() {
+            Ok(done) => self.messages.info(done),
+            Err(err) => self.messages.report(err),
+        }
+    }
+
+    //Lists what can be undone and redone, most recent first
+    pub fn show_history(&mut self) {
+        let mut lines = vec![String::from("Undo (u):")];
+        if self.journal.undo.is_empty() {
+            lines.push(String::from("  nothing to undo"));
+        }
+        for operation in self.journal.undo.iter().rev() {
+            lines.push(format!("  {}", operation.describe()));
+        }
+
+        lines.push(String::new());
+        lines.push(String::from("Redo (Ctrl-r):"));
+        if self.journal.redo.is_empty() {
+            lines.push(String::from("  nothing to redo"));
+        }
+        for operation in self.journal.redo.iter().rev() {
+            lines.push(format!("  {}", operation.describe()));
+        }
+
+        self.show_text(String::from("History"), lines);
+    }
+
+    //Shows everything known about the selected entry
+    pub fn show_info(&mut self) {
+        let item = match self.selection_index {
+            Some(index) => self.directory_contents[index].clone(),
+            None => {
+                self.messages.report(Error::NothingSelected);
+                return;
+            }
+        };
+
+        self.show_text(file_ops::display_name(item.name()), item.describe());
+    }
+
+    pub fn show_text(&mut self, title: String, lines: Vec<String>) {
+        self.text_view = Some(TextView {
+            title,
+            lines,
+            scroll: 0,
+        });
+        self.change_mode(Mode::View);
+    }
+
+    pub fn close_text(&mut self) {
+        self.text_view = None;
+        self.change_mode(Mode::Browse);
+    }
+
+    pub fn scroll_text(&mut self, down: bool) {
+        if let Some(view) = &mut self.text_view {
+            if down {
+                if view.scroll + 1 < view.lines.len() {
+                    view.scroll += 1;
+                }
+            } else {
+                view.scroll = view.scroll.saturating_sub(1);
+            }
+        }
+    }
+
+    pub fn toggle_preview(&mut self) {
+        self.show_preview = !self.show_preview;
+    }
+
+    //Picks up finished previews and asks for one of the selected entry if needed. True if a new
+    //preview came in.
+    pub fn update_preview(&mut self) -> bool {
+        if !self.show_preview {
+            return false;
+        }
+
+        let contents = &self.directory_contents;
+        let selected = self.selection_index.and_then(|index| contents.get(index));
+        if let Some(watcher) = &mut self.watcher {
+            watcher.watch_previewed(
+                selected
+                    .filter(|item| item.is_directory())
+                    .map(|item| item.path()),
+            );
+        }
+        self.previewer.update(selected, self.show_hidden, self.sort)
+    }
+
+    //Opens the fuzzy finder over everything under the current directory
+    pub fn open_finder(&mut self) {
+        self.finder = Some(Finder::new(
+            self.current_directory.clone(),
+            self.show_hidden,
+            self.respect_ignore_files,
+        ));
+        self.change_mode(Mode::Find);
+    }
+
+    //Dropping the finder also stops its walk
+    pub fn close_finder(&mut self) {
+        self.finder = None;
+        self.change_mode(Mode::Browse);
+    }
+
+    //True while the finder is still finding files
+    pub fn update_finder(&mut self) -> bool {
+        match &mut self.finder {
+            Some(finder) => {
+                finder.update();
+                finder.walking
+            }
+            None => false,
+        }
+    }
+
+    //Goes to the directory of the chosen file and selects it
+    pub fn accept_finder(&mut self) {
+        let path = self
+            .finder
+            .as_ref()
+            .and_then(|finder| finder.selected_path());
+        self.close_finder();
+
+        if let Some(path) = path {
+            self.go_to_file(&path);
+        }
+    }
+
+    //Opens the directory `path` is in and selects it there
+    pub fn go_to_file(&mut self, path: &Path) {
+        let (directory, name) = match (path.parent(), path.file_name()) {
+            (Some(directory), Some(name)) => (directory, name),
+            _ => return,
+        };
+
+        if !self.change_directory(directory.to_path_buf()) {
+            return;
+        }
+        //The filter may hide the file, in which case it's dropped so the file can be shown
+        if !self.select_file_named(name) && self.filter.is_some() {
+            self.filter = None;
+            self.apply_filters();
+        }
+        if !self.select_file_named(name) {
+            self.messages.warn(format!(
+                "{} isn't shown in the listing",
+                file_ops::display_name(name)
+            ));
+        }
+    }
+
+    //`:grep <pattern>` searches file contents; with no pattern the last results are shown again
+    pub fn start_grep(&mut self, arguments: &[String]) {
+        if arguments.is_empty() {
+            if self.grep.is_some() {
+                self.change_mode(Mode::Grep);
+            } else {
+                self.messages.report(Error::Usage(":grep <pattern>"));
+            }
+            return;
+        }
+
+        match Grep::new(
+            self.current_directory.clone(),
+            &arguments.join(" "),
+            self.show_hidden,
+            self.respect_ignore_files,
+        ) {
+            Ok(grep) => {
+                self.grep = Some(grep);
+                self.change_mode(Mode::Grep);
+            }
+            Err(err) => self.messages.report(Error::InvalidPattern(err)),
+        }
+    }
+
+    //True while the search is still running
+    pub fn update_grep(&mut self) -> bool {
+        match &mut self.grep {
+            Some(grep) => {
+                grep.update();
+                grep.searching
             }
+            None => false,
+        }
+    }
+
+    //Catches up with background work, the directory and timers. True if anything shown may
+    //have changed.
+    pub fn update(&mut self) -> Result<bool, std::io::Error> {
+        let mut changed = self.update_jobs()?;
+        changed |= self.update_finder();
+        changed |= self.update_grep();
+        changed |= self.rescan_if_unwatched()?;
+        changed |= self.update_preview();
+        changed |= self.messages.expire();
+        Ok(changed)
+    }
+
+    //How long the main loop can sleep when nothing happens: not long while work is running, to
+    //show its progress, and forever when idle unless the directory has to be read again now and
+    //then or a message is waiting to be cleared
+    pub fn next_timeout(&self) -> Option<Duration> {
+        let busy = !self.jobs.jobs.is_empty()
+            || self.finder.as_ref().is_some_and(|finder| finder.walking)
+            || self.grep.as_ref().is_some_and(|grep| grep.searching);
+        let mut timeout = if busy {
+            Some(BUSY_INTERVAL)
+        } else if !self.watching {
+            Some(RESCAN_INTERVAL.saturating_sub(self.last_read.elapsed()))
         } else {
             None
+        };
+
+        if let Some(left) = self.messages.time_left() {
+            timeout = Some(timeout.map_or(left, |timeout| timeout.min(left)));
+        }
+        timeout
+    }
+
+    //Reads the directory again now and then if its changes aren't being reported
+    fn rescan_if_unwatched(&mut self) -> Result<bool, std::io::Error> {
+        if self.watching || self.last_read.elapsed() < RESCAN_INTERVAL {
+            return Ok(false);
+        }
+        self.refresh()?;
+        Ok(true)
+    }
+
+    //Reads the directory again. If it can't be read any more, e.g. because it was deleted, goes
+    //up to the nearest directory that can.
+    pub fn refresh(&mut self) -> Result<(), std::io::Error> {
+        let err = match self.populate_files() {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        let unreadable = self.current_directory.clone();
+        let mut directory = unreadable.clone();
+        loop {
+            if !directory.pop() {
+                self.current_directory = unreadable;
+                return Err(err);
+            }
+            self.current_directory = directory.clone();
+            if self.populate_files().is_ok() {
+                break;
+            }
+        }
+
+        self.selection_index = Some(0);
+        self.clear_marks();
+        self.messages.error(format!(
+            "{} can't be read any more: {}. Went up to {}",
+            file_ops::display_path(&unreadable),
+            err,
+            file_ops::display_path(&directory)
+        ));
+        Ok(())
+    }
+
+    //Applies changes reported in the listed directory entry by entry, rather than reading it all
+    //again. The selection stays on the same entry, and follows it if it's renamed.
+    pub fn apply_changes(&mut self, changes: Vec<watch::Change>) -> Result<(), std::io::Error> {
+        let selected = self.get_selected_file_path();
+        let mut updated = HashSet::new();
+        let mut follow = None;
+        let mut rescan = false;
+
+        //Changes in the previewed directory need nothing here, as the preview notices itself
+        let directory = self.current_directory.clone();
+        for change in changes
+            .into_iter()
+            .filter(|change| change.directory == directory)
+        {
+            let path = directory.join(&change.name);
+            //The ignore rules themselves changed
+            rescan |= self.respect_ignore_files
+                && (change.name == ".gitignore" || change.name == ".ignore");
+
+            match change.kind {
+                ChangeKind::Rescan => rescan = true,
+                ChangeKind::Renamed(from) => {
+                    let from = directory.join(from);
+                    if self.marked_files.remove(&from) {
+                        self.marked_files.insert(path.clone());
+                    }
+                    if selected.as_ref() == Some(&from) {
+                        follow = Some(path.clone());
+                    }
+                    updated.insert(from);
+                    updated.insert(path);
+                }
+                ChangeKind::Changed => {
+                    updated.insert(path);
+                }
+            }
+        }
+
+        //The trash's listing also depends on its info files, so it's simplest to read it all
+        if rescan || self.browsed_trash().is_some() {
+            return self.refresh();
+        }
+        if updated.is_empty() {
+            return Ok(());
+        }
+
+        //Whatever is there now replaces what was listed, including nothing for deleted entries
+        self.all_contents
+            .retain(|item| !updated.contains(item.path()));
+        let items: Vec<DirectoryItem> = updated
+            .iter()
+            .filter_map(|path| DirectoryItem::from_path(path).ok())
+            .collect();
+        if self.respect_ignore_files {
+            self.ignored_files.retain(|path| !updated.contains(path));
+            self.ignored_files
+                .extend(file_ops::get_ignored_files(&self.current_directory, &items));
+        }
+        self.all_contents.extend(items);
+
+        self.apply_filters();
+        if let Some(path) = follow {
+            if let Some(index) = self
+                .directory_contents
+                .iter()
+                .position(|item| item.path() == path)
+            {
+                self.selection_index = Some(index);
+            }
+        }
+        Ok(())
+    }
+
+    //Leaves the results and stops the search; `:grep` alone shows what it had found
+    pub fn close_grep(&mut self) {
+        if let Some(grep) = &self.grep {
+            grep.cancel();
+        }
+        self.change_mode(Mode::Browse);
+    }
+
+    //Shows the file with the selected match in the listing
+    pub fn grep_go_to(&mut self) {
+        if let Some((path, _)) = self.grep.as_ref().and_then(|grep| grep.selected_match()) {
+            self.close_grep();
+            self.go_to_file(&path);
+        }
+    }
+
+    //Opens the file with the selected match in $EDITOR at the matching line
+    pub fn grep_edit(&mut self) {
+        if let Some((path, line_number)) = self.grep.as_ref().and_then(|grep| grep.selected_match())
+        {
+            self.foreground_command = Some(opener::editor_command(&path, Some(line_number)));
+        }
+    }
+
+    //Runs `command` with the shell in the current directory, after filling in the placeholders.
+    //A trailing '&' runs it in the background as a job, with its output going to the log.
+    pub fn run_shell(&mut self, command: &str) {
+        let command = command.trim();
+        let (command, background) = match command.strip_suffix('&') {
+            //"&&" is the shell's own operator
+            Some(rest) if !rest.ends_with('&') => (rest.trim_end(), true),
+            _ => (command, false),
+        };
+        if command.is_empty() {
+            self.messages.report(Error::Usage(":!<command>[ &]"));
+            return;
+        }
+
+        let placeholders = shell::Placeholders {
+            selected: self.get_selected_file_path(),
+            targets: self.get_target_paths(),
+            directory: self.current_directory.clone(),
+        };
+        let script = match shell::expand(command, &placeholders) {
+            Ok(script) => script,
+            Err(err) => {
+                self.messages.warn(err);
+                return;
+            }
+        };
+
+        if !background {
+            self.foreground_command =
+                Some(shell::foreground_command(&script, &self.current_directory));
+            return;
+        }
+
+        let job_id = self.jobs.next_id();
+        if let Ok(mut log) = self.shell_log.lock() {
+            log.entries.push(shell::LogEntry {
+                job_id,
+                command: command.to_string(),
+                lines: Vec::new(),
+                status: None,
+            });
         }
+        let directory = self.current_directory.clone();
+        let log = Arc::clone(&self.shell_log);
+        self.jobs
+            .spawn(JobKind::Shell, command.to_string(), move |progress| {
+                shell::run_background(script, directory, log, job_id, progress)
+            });
+    }
+
+    pub fn show_log(&mut self) {
+        let lines = match self.shell_log.lock() {
+            Ok(log) => log.describe(),
+            Err(_) => vec![String::from("The log is unavailable")],
+        };
+        self.show_text(String::from("Log"), lines);
     }
 
-    pub fn load_selected_into_file_buffer(&mut self) {
-        let result = file_ops::read_file(self);
-        self.file_buffer = result.0;
-        self.buffered_file_name = result.1;
+    pub fn show_messages(&mut self) {
+        let lines = self.messages.describe();
+        self.show_text(String::from("Messages"), lines);
     }
 
-    pub fn get_buffered_file(&self) -> (Option<Vec<u8>>, Option<String>) {
-        (self.file_buffer.clone(), self.buffered_file_name.clone())
+    pub fn cancel_job(&mut self, id: Option<usize>) {
+        match self.jobs.find(id) {
+            Some(job) => job.progress.cancel(),
+            None => self.messages.warn("No such job"),
+        }
     }
 
-    pub fn write_buffered_file(&mut self) {
-        let result = file_ops::write_file(self);
-        if let Ok(_) = result {
-            self.buffered_file_name = None;
-            self.file_buffer = None;
+    pub fn pause_job(&mut self, id: Option<usize>, paused: bool) {
+        match self.jobs.find(id) {
+            Some(job) => job.progress.set_paused(paused),
+            None => self.messages.warn("No such job"),
         }
     }
 }
 
-#[derive(PartialEq)]
+//Short description of a set of paths for job titles, e.g. "'notes.txt'" or "3 items"
+pub fn describe_paths(paths: &[PathBuf]) -> String {
+    match paths {
+        [path] => format!("'{}'", file_ops::display_file_name(path)),
+        _ => format!("{} items", paths.len()),
+    }
+}
+
+//Files waiting to be pasted. Only paths are held; the data is read when pasting.
+pub struct Clipboard {
+    pub paths: Vec<PathBuf>,
+    pub cut: bool,
+}
+
+//A destructive operation waiting for the user to confirm it
+pub enum Action {
+    Trash(Trash, Vec<PathBuf>),
+    Delete(Vec<PathBuf>),
+    PurgeFromTrash(Trash, Vec<PathBuf>),
+    EmptyTrash(Trash),
+}
+
+pub struct Confirmation {
+    pub title: String,
+    //The question, which the size of what it affects is added to once that's been measured
+    question: String,
+    undoable: bool,
+    measurement: Measurement,
+    pub action: Action,
+}
+
+impl Confirmation {
+    //Asks straight away; what the action would affect is measured in the background so the
+    //prompt can say how much is at stake
+    pub fn new(action: Action, waker: Waker) -> Confirmation {
+        let (title, question, undoable, paths): (_, _, _, PathList) = match &action {
+            Action::Trash(_, paths) => {
+                let paths = paths.clone();
+                (
+                    "Move to trash",
+                    format!("Move {} to the trash?", describe_paths(&paths)),
+                    true,
+                    Box::new(move || paths),
+                )
+            }
+            Action::Delete(paths) | Action::PurgeFromTrash(_, paths) => {
+                let paths = paths.clone();
+                (
+                    "Delete permanently",
+                    format!("Permanently delete {}?", describe_paths(&paths)),
+                    false,
+                    Box::new(move || paths),
+                )
+            }
+            Action::EmptyTrash(trash) => {
+                let trash = trash.clone();
+                (
+                    "Empty trash",
+                    String::from("Permanently delete everything in the trash?"),
+                    false,
+                    Box::new(move || trash.contents().unwrap_or_default()),
+                )
+            }
+        };
+
+        Confirmation {
+            title: String::from(title),
+            question,
+            undoable,
+            measurement: Measurement::start(paths, waker),
+            action,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let size = match *self.measurement.size.lock().unwrap() {
+            Some(size) => size.describe(),
+            None => String::from("Measuring..."),
+        };
+        if self.undoable {
+            format!("{}\n{}", self.question, size)
+        } else {
+            format!("{}\n{}\nThis can't be undone.", self.question, size)
+        }
+    }
+}
+
+//The size of the trees a confirmation is about, worked out on another thread. Dropping it
+//stops the walk, so closing the dialog doesn't leave a large tree being read.
+struct Measurement {
+    size: Arc<Mutex<Option<file_ops::TreeSize>>>,
+    stop: Arc<AtomicBool>,
+}
+
+//Lists what to measure. Called on the measuring thread, as listing the trash reads the disk.
+type PathList = Box<dyn FnOnce() -> Vec<PathBuf> + Send>;
+
+impl Measurement {
+    //Measures the paths `list` gives
+    fn start(list: PathList, waker: Waker) -> Measurement {
+        let measurement = Measurement {
+            size: Arc::default(),
+            stop: Arc::default(),
+        };
+
+        let size = measurement.size.clone();
+        let stop = measurement.stop.clone();
+        thread::spawn(move || {
+            let paths = list();
+
+            let mut total = file_ops::TreeSize::default();
+            for path in &paths {
+                total.add(file_ops::measure_until(path, &stop));
+            }
+            if !stop.load(Ordering::Relaxed) {
+                *size.lock().unwrap() = Some(total);
+                waker.wake();
+            }
+        });
+
+        measurement
+    }
+}
+
+impl Drop for Measurement {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+//Read only text shown over the file list, such as the undo history
+pub struct TextView {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub scroll: usize,
+}
+
+#[derive(Debug, PartialEq)]
 pub enum Mode {
     Browse,
     Command,
-    _Select,
+    Select,
+    Conflict,
+    Confirm,
+    View,
+    Search,
+    Find,
+    Grep,
 }
@@ -0,0 +1,106 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+
+use crate::file_ops;
+
+pub const USAGE: &str = "Usage: tfex [OPTIONS] [PATH]
+
+Opens PATH (or the current working directory) in the file explorer.
+If PATH is a file, its directory is opened with the file selected.
+
+Options:
+    -s, --select <NAME>    Select the entry NAME once the directory is open
+    -h, --help             Print this help and exit
+    --                     Treat everything after it as the path";
+
+pub struct Args {
+    pub directory: PathBuf,
+    pub select: Option<OsString>,
+    pub help: bool,
+}
+
+//Arguments are taken as OsStrings so paths that aren't valid UTF-8 can still be opened
+pub fn parse<I: Iterator<Item = OsString>>(mut args: I) -> Result<Args, String> {
+    let mut path: Option<OsString> = None;
+    let mut select: Option<OsString> = None;
+    let mut help = false;
+    //After "--" everything is a path, even if it starts with '-'
+    let mut options_ended = false;
+
+    while let Some(arg) = args.next() {
+        if options_ended {
+            set_path(&mut path, arg)?;
+            continue;
+        }
+
+        let option = arg.to_str().unwrap_or_default();
+        match option {
+            "-h" | "--help" => help = true,
+            "-s" | "--select" => match args.next() {
+                Some(name) => select = Some(name),
+                None => return Err(format!("{} requires a file name", option)),
+            },
+            "--" => options_ended = true,
+            _ if arg.as_bytes().starts_with(b"--select=") => {
+                select = Some(OsString::from_vec(arg.as_bytes()[9..].to_vec()))
+            }
+            _ if arg.as_bytes().starts_with(b"-") && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", file_ops::display_name(&arg)))
+            }
+            _ => set_path(&mut path, arg)?,
+        }
+    }
+
+    if help {
+        return Ok(Args {
+            directory: PathBuf::new(),
+            select: None,
+            help,
+        });
+    }
+
+    let requested = match path {
+        Some(path) => PathBuf::from(path),
+        None => env::current_dir()
+            .map_err(|err| format!("Cannot read the current directory: {}", err))?,
+    };
+
+    let resolved = fs::canonicalize(&requested).map_err(|err| {
+        format!(
+            "Cannot open {}: {}",
+            file_ops::display_path(&requested),
+            err
+        )
+    })?;
+
+    //A file was given, so open its directory and select it
+    if !resolved.is_dir() {
+        if select.is_none() {
+            select = resolved.file_name().map(OsString::from);
+        }
+
+        let parent = resolved.parent().map(PathBuf::from).unwrap_or_default();
+        return Ok(Args {
+            directory: parent,
+            select,
+            help,
+        });
+    }
+
+    Ok(Args {
+        directory: resolved,
+        select,
+        help,
+    })
+}
+
+fn set_path(path: &mut Option<OsString>, arg: OsString) -> Result<(), String> {
+    if path.is_some() {
+        return Err(String::from("Only one path can be given"));
+    }
+    *path = Some(arg);
+    Ok(())
+}
@@ -1,26 +1,73 @@
 use crate::app::App;
+use crate::error::Error;
 use crate::file_ops;
+use crate::journal::{Kind, Operation, Step};
 
 pub fn process_command(command_string: String, app: &mut App) {
+    //The rest of the line goes to the shell as it was typed
+    if let Some(shell_command) = command_string.strip_prefix('!') {
+        app.run_shell(shell_command);
+        return;
+    }
+
     //split command buffer
     let split_command: Vec<String> = command_string
-        .trim_start_matches(":")
         .split_ascii_whitespace()
         .map(|f| f.to_string())
         .collect();
 
-    let current_dir = &app.current_directory.to_str().unwrap();
+    //Enter on an empty line just leaves the command box
+    if split_command.is_empty() {
+        return;
+    }
+
+    let current_dir = &app.current_directory.clone();
 
     match split_command[0].to_ascii_uppercase().as_ref() {
-        "RENAME" | "REN" => app.error = file_ops::rename_file(&split_command, current_dir, &app),
-        "DELETE" | "DEL" => {
-            app.error = {
-                let result = file_ops::delete_file(&app);
-                app.move_selection_up();
-                result
-            }
-        }
-        "DIRECTORY" | "DIR" => app.error = file_ops::create_directory(&split_command, current_dir),
-        _ => app.error = Some(String::from("Not a command")),
+        "RENAME" | "REN" => match file_ops::rename_file(&split_command, current_dir, app) {
+            Ok((from, to)) => app
+                .journal
+                .record(Operation::new(Kind::Rename, vec![Step::new(from, to)])),
+            Err(err) => app.messages.report(err),
+        },
+        "DELETE" | "DEL" => app.trash_selected(),
+        "PURGE" => app.purge_selected(),
+        "TRASH" => app.open_trash(),
+        "RESTORE" => app.restore_selected(),
+        "EMPTYTRASH" => app.empty_trash(),
+        "DIRECTORY" | "DIR" => match file_ops::create_directory(&split_command, current_dir) {
+            Ok(path) => app.journal.record(Operation::new(
+                Kind::CreateDirectory,
+                vec![Step::created(path)],
+            )),
+            Err(err) => app.messages.report(err),
+        },
+        "HISTORY" => app.show_history(),
+        "LOG" => app.show_log(),
+        "MESSAGES" | "MES" => app.show_messages(),
+        "INFO" => app.show_info(),
+        "SORT" => match split_command.get(1).map(|arg| arg.as_str()) {
+            Some("reverse") => app.reverse_sort(),
+            Some("dirsfirst") => app.toggle_directories_first(),
+            _ => app.set_sort(&split_command[1..]),
+        },
+        "GREP" => app.start_grep(&split_command[1..]),
+        "FILTER" => app.set_filter(&split_command[1..]),
+        "HIDDEN" => app.toggle_hidden(),
+        "PREVIEW" => app.toggle_preview(),
+        "GITIGNORE" => app.toggle_ignore_files(),
+        "CANCEL" => app.cancel_job(parse_job_id(&split_command)),
+        "PAUSE" => app.pause_job(parse_job_id(&split_command), true),
+        "RESUME" => app.pause_job(parse_job_id(&split_command), false),
+        _ => app
+            .messages
+            .report(Error::UnknownCommand(split_command[0].clone())),
     };
 }
+
+//Optional job number argument, e.g. ":cancel 2" or ":cancel #2"
+fn parse_job_id(command: &[String]) -> Option<usize> {
+    command
+        .get(1)
+        .and_then(|id| id.trim_start_matches('#').parse().ok())
+}
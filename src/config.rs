@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::highlight;
+use crate::opener::{self, Rule};
+use crate::sort::{SortKey, SortOrder};
+
+//Operations that can be set to ask for confirmation first
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Delete,
+    Purge,
+    EmptyTrash,
+}
+
+impl Operation {
+    fn from_name(name: &str) -> Option<Operation> {
+        match name {
+            "delete" => Some(Operation::Delete),
+            "purge" => Some(Operation::Purge),
+            "emptytrash" => Some(Operation::EmptyTrash),
+            _ => None,
+        }
+    }
+}
+
+pub struct Config {
+    pub confirm: HashSet<Operation>,
+    pub sort: SortOrder,
+    pub show_hidden: bool,
+    pub respect_ignore_files: bool,
+    pub preview: bool,
+    //Colours for highlighting previews, or None to not highlight them
+    pub theme: Option<String>,
+    //How to open files, tried in order before the defaults
+    pub openers: Vec<Rule>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            confirm: [Operation::Delete, Operation::Purge, Operation::EmptyTrash]
+                .iter()
+                .cloned()
+                .collect(),
+            sort: SortOrder::default(),
+            show_hidden: true,
+            respect_ignore_files: false,
+            preview: false,
+            theme: Some(String::from(highlight::DEFAULT_THEME)),
+            openers: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn confirms(&self, operation: Operation) -> bool {
+        self.confirm.contains(&operation)
+    }
+}
+
+//$XDG_CONFIG_HOME/tfex/config, falling back to ~/.config/tfex/config
+pub fn config_path() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(path) if PathBuf::from(&path).is_absolute() => {
+            Some(PathBuf::from(path).join("tfex/config"))
+        }
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/tfex/config")),
+    }
+}
+
+//$XDG_DATA_HOME, falling back to ~/.local/share, where the trash and command history are kept
+pub fn data_home() -> io::Result<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(path) if PathBuf::from(&path).is_absolute() => Ok(PathBuf::from(path)),
+        _ => match env::var_os("HOME") {
+            Some(home) => Ok(PathBuf::from(home).join(".local/share")),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "neither XDG_DATA_HOME nor HOME is set",
+            )),
+        },
+    }
+}
+
+//Reads the config file, using the defaults for anything it doesn't set or if there is no file
+pub fn load() -> Result<Config, String> {
+    let path = match config_path() {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents).map_err(|err| format!("{}: {}", path.display(), err)),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+}
+
+//Parses "key = value" lines. Blank lines and lines starting with '#' are ignored.
+pub fn parse(contents: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Err(format!("line {}: expected 'key = value'", number + 1)),
+        };
+
+        match key {
+            "confirm" => config.confirm = parse_operations(value, number + 1)?,
+            "sort" => parse_sort(value, &mut config.sort)
+                .map_err(|err| format!("line {}: {}", number + 1, err))?,
+            "directories_first" => {
+                config.sort.directories_first =
+                    parse_bool(value).map_err(|err| format!("line {}: {}", number + 1, err))?
+            }
+            "show_hidden" => {
+                config.show_hidden =
+                    parse_bool(value).map_err(|err| format!("line {}: {}", number + 1, err))?
+            }
+            "gitignore" => {
+                config.respect_ignore_files =
+                    parse_bool(value).map_err(|err| format!("line {}: {}", number + 1, err))?
+            }
+            "preview" => {
+                config.preview =
+                    parse_bool(value).map_err(|err| format!("line {}: {}", number + 1, err))?
+            }
+            "theme" => {
+                config.theme =
+                    parse_theme(value).map_err(|err| format!("line {}: {}", number + 1, err))?
+            }
+            _ if key.starts_with("open ") => config.openers.push(
+                opener::parse_rule(&key["open ".len()..], value)
+                    .map_err(|err| format!("line {}: {}", number + 1, err))?,
+            ),
+            _ => return Err(format!("line {}: unknown option '{}'", number + 1, key)),
+        }
+    }
+
+    Ok(config)
+}
+
+//A comma separated list of operation names, or "none"
+fn parse_operations(value: &str, line: usize) -> Result<HashSet<Operation>, String> {
+    let mut operations = HashSet::new();
+
+    for name in value.split(',').map(str::trim) {
+        if name.is_empty() || name == "none" {
+            continue;
+        }
+
+        match Operation::from_name(name) {
+            Some(operation) => {
+                operations.insert(operation);
+            }
+            None => return Err(format!("line {}: unknown operation '{}'", line, name)),
+        }
+    }
+
+    Ok(operations)
+}
+
+//A sort key optionally followed by "asc" or "desc", e.g. "size desc". Also used by :sort.
+pub fn parse_sort(value: &str, sort: &mut SortOrder) -> Result<(), String> {
+    let mut words = value.split_whitespace();
+
+    let key = words
+        .next()
+        .ok_or_else(|| String::from("expected a sort key"))?;
+    sort.key = SortKey::from_name(key).ok_or_else(|| {
+        format!(
+            "unknown sort key '{}', expected one of {}",
+            key,
+            SortKey::ALL
+                .iter()
+                .map(|key| key.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+
+    match words.next() {
+        None | Some("asc") => sort.descending = false,
+        Some("desc") => sort.descending = true,
+        Some(other) => return Err(format!("expected 'asc' or 'desc', not '{}'", other)),
+    }
+
+    Ok(())
+}
+
+//One of the built in themes, or "none" to turn highlighting off
+fn parse_theme(value: &str) -> Result<Option<String>, String> {
+    if value == "none" {
+        return Ok(None);
+    }
+
+    let mut names = highlight::theme_names();
+    if names.iter().any(|name| name == value) {
+        Ok(Some(value.to_string()))
+    } else {
+        names.sort();
+        Err(format!(
+            "unknown theme '{}', expected none or one of {}",
+            value,
+            names.join(", ")
+        ))
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected true or false, not '{}'", value)),
+    }
+}
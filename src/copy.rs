@@ -0,0 +1,352 @@
+use std::fs;
+use std::fs::{File, FileTimes, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::fs::{symlink, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::file_ops;
+use crate::jobs::Progress;
+
+//Size of the buffer used to stream file contents
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Default)]
+pub struct CopyReport {
+    pub files: u64,
+    pub directories: u64,
+    pub bytes: u64,
+    pub errors: Vec<(PathBuf, io::Error)>,
+    pub progress: Arc<Progress>,
+}
+
+impl CopyReport {
+    pub fn new(progress: Arc<Progress>) -> CopyReport {
+        CopyReport {
+            progress,
+            ..CopyReport::default()
+        }
+    }
+
+    fn fail(&mut self, path: &Path, error: io::Error) {
+        //Everything fails once a job is cancelled; the job reports that once instead
+        if !self.progress.is_cancelled() {
+            self.errors.push((path.to_path_buf(), error));
+        }
+    }
+}
+
+//Copies a file, symlink or whole directory tree to `destination`.
+//Errors are recorded in the report per file and the rest of the tree is still copied.
+pub fn copy_path(source: &Path, destination: &Path, report: &mut CopyReport) {
+    if source.is_dir() && destination.starts_with(source) {
+        report.fail(
+            source,
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot copy a directory into itself",
+            ),
+        );
+        return;
+    }
+
+    copy_entry(source, destination, report);
+}
+
+//Moves `source` to `destination`. A rename is used when both are on the same filesystem,
+//otherwise the tree is copied, verified against the source, and only then is the source removed.
+//With `replace`, an existing destination is overwritten and directories are merged.
+pub fn move_path(source: &Path, destination: &Path, replace: bool, report: &mut CopyReport) {
+    if source == destination {
+        return;
+    }
+
+    if fs::symlink_metadata(destination).is_ok() {
+        if !replace {
+            report.fail(
+                source,
+                io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", file_ops::display_path(destination)),
+                ),
+            );
+            return;
+        }
+
+        //A rename can't replace a non-empty directory, so merge into it instead
+        if is_real_directory(source) && is_real_directory(destination) {
+            return copy_verify_remove(source, destination, report);
+        }
+
+        if let Err(err) = prepare_overwrite(source, destination) {
+            return report.fail(destination, err);
+        }
+    }
+
+    match fs::rename(source, destination) {
+        Ok(_) => {
+            let size = file_ops::measure(destination);
+            report.files += size.files;
+            report.progress.add_files(size.files);
+            report.progress.add_bytes(size.bytes);
+            return;
+        }
+        Err(err) if err.kind() != io::ErrorKind::CrossesDevices => return report.fail(source, err),
+        Err(_) => {}
+    }
+
+    copy_verify_remove(source, destination, report);
+}
+
+fn copy_verify_remove(source: &Path, destination: &Path, report: &mut CopyReport) {
+    let errors_before = report.errors.len();
+    copy_path(source, destination, report);
+    if report.errors.len() != errors_before {
+        return;
+    }
+
+    if let Err(err) = verify_copy(source, destination) {
+        return report.fail(source, err);
+    }
+
+    if let Err(err) = file_ops::remove_path(source) {
+        report.fail(source, err);
+    }
+}
+
+//Removes an existing `destination` that `source` can't simply be written over: anything other
+//than a regular file replaced by a regular file, or a directory merged into a directory.
+//Symlinks are always removed so the copy doesn't write through them.
+pub fn prepare_overwrite(source: &Path, destination: &Path) -> io::Result<()> {
+    let existing = match fs::symlink_metadata(destination) {
+        Ok(existing) => existing,
+        Err(_) => return Ok(()),
+    };
+    let incoming = fs::symlink_metadata(source)?;
+
+    let both_files = existing.file_type().is_file() && incoming.file_type().is_file();
+    let both_directories = existing.file_type().is_dir() && incoming.file_type().is_dir();
+
+    if both_files || both_directories {
+        Ok(())
+    } else {
+        file_ops::remove_path(destination)
+    }
+}
+
+fn is_real_directory(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|metadata| metadata.is_dir())
+        .unwrap_or(false)
+}
+
+//Checks that `destination` holds the same tree as `source`: same kinds, link targets and contents
+pub fn verify_copy(source: &Path, destination: &Path) -> io::Result<()> {
+    let source_metadata = fs::symlink_metadata(source)?;
+    let destination_metadata = fs::symlink_metadata(destination)?;
+    let mismatch = |what: &str| {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "copy of {} differs: {}",
+                file_ops::display_path(source),
+                what
+            ),
+        ))
+    };
+
+    if source_metadata.file_type() != destination_metadata.file_type() {
+        return mismatch("file type");
+    }
+
+    if source_metadata.file_type().is_symlink() {
+        if fs::read_link(source)? != fs::read_link(destination)? {
+            return mismatch("link target");
+        }
+    } else if source_metadata.is_dir() {
+        //The destination may hold extra entries when it was merged into
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            verify_copy(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else if source_metadata.len() != destination_metadata.len() {
+        return mismatch("size");
+    } else if !same_contents(source, destination)? {
+        return mismatch("contents");
+    }
+
+    Ok(())
+}
+
+//Reads both files back and compares them byte for byte
+fn same_contents(first: &Path, second: &Path) -> io::Result<bool> {
+    let mut first = File::open(first)?;
+    let mut second = File::open(second)?;
+    let mut first_buffer = vec![0u8; CHUNK_SIZE];
+    let mut second_buffer = vec![0u8; CHUNK_SIZE];
+
+    loop {
+        let read = first.read(&mut first_buffer)?;
+        if read == 0 {
+            //The second one may have grown since the sizes were compared
+            return Ok(second.read(&mut second_buffer[..1])? == 0);
+        }
+        second.read_exact(&mut second_buffer[..read])?;
+        if first_buffer[..read] != second_buffer[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+fn copy_entry(source: &Path, destination: &Path, report: &mut CopyReport) {
+    if report.progress.is_cancelled() {
+        return;
+    }
+
+    let metadata = match fs::symlink_metadata(source) {
+        Ok(metadata) => metadata,
+        Err(err) => return report.fail(source, err),
+    };
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        let result = fs::read_link(source).and_then(|target| symlink(target, destination));
+        match result {
+            Ok(_) => {
+                report.files += 1;
+                report.progress.add_files(1);
+            }
+            Err(err) => report.fail(source, err),
+        }
+    } else if file_type.is_dir() {
+        copy_directory(source, destination, &metadata, report);
+    } else if file_type.is_file() {
+        match copy_file(source, destination, &metadata, &report.progress) {
+            Ok(bytes) => {
+                report.files += 1;
+                report.bytes += bytes;
+                report.progress.add_files(1);
+            }
+            Err(err) => {
+                //Don't leave a partial file behind when the job was cancelled
+                if report.progress.is_cancelled() {
+                    let _ = fs::remove_file(destination);
+                }
+                report.fail(source, err);
+            }
+        }
+    } else {
+        report.fail(
+            source,
+            io::Error::new(io::ErrorKind::InvalidInput, "unsupported file type"),
+        );
+    }
+}
+
+fn copy_directory(
+    source: &Path,
+    destination: &Path,
+    metadata: &fs::Metadata,
+    report: &mut CopyReport,
+) {
+    //Merging into an existing directory, whose entries may need replacing like the top level's
+    let mut merging = false;
+    if let Err(err) = fs::create_dir(destination) {
+        if err.kind() != io::ErrorKind::AlreadyExists || !is_real_directory(destination) {
+            return report.fail(source, err);
+        }
+        merging = true;
+    }
+
+    let entries = match fs::read_dir(source) {
+        Ok(entries) => entries,
+        Err(err) => return report.fail(source, err),
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                report.fail(source, err);
+                continue;
+            }
+        };
+
+        let child = destination.join(entry.file_name());
+        if merging {
+            if let Err(err) = prepare_overwrite(&entry.path(), &child) {
+                report.fail(&child, err);
+                continue;
+            }
+        }
+        copy_entry(&entry.path(), &child, report);
+    }
+
+    //Permissions and times are applied last, so a read-only directory can still be filled
+    //and creating its children doesn't change the copied mtime
+    if let Err(err) = preserve_attributes(destination, metadata) {
+        report.fail(destination, err);
+    }
+
+    report.directories += 1;
+}
+
+fn copy_file(
+    source: &Path,
+    destination: &Path,
+    metadata: &fs::Metadata,
+    progress: &Progress,
+) -> io::Result<u64> {
+    //Creating the destination would truncate the source if they are the same file
+    if let Ok(existing) = fs::metadata(destination) {
+        if existing.dev() == metadata.dev() && existing.ino() == metadata.ino() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "source and destination are the same file",
+            ));
+        }
+    }
+
+    let mut reader = File::open(source)?;
+    //Never write through a symlink that appeared at the destination
+    let mut writer = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(destination)?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut copied: u64 = 0;
+
+    loop {
+        progress.checkpoint()?;
+
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+        progress.add_bytes(read as u64);
+    }
+
+    writer.flush()?;
+    drop(writer);
+
+    preserve_attributes(destination, metadata)?;
+
+    Ok(copied)
+}
+
+fn preserve_attributes(destination: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    File::open(destination)?.set_times(times)?;
+
+    fs::set_permissions(destination, metadata.permissions())
+}
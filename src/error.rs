@@ -0,0 +1,135 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::file_ops;
+use crate::messages::Severity;
+
+//Something a command or file operation couldn't do, with what it was doing and to what
+#[derive(Debug)]
+pub enum Error {
+    //A file system call failed, e.g. operation "rename" on the entry being renamed. The
+    //source holds the io::ErrorKind.
+    Io {
+        operation: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    //A command was given the wrong arguments; holds how it should be used
+    Usage(&'static str),
+    NothingSelected,
+    //An operation that works on one entry at a time was given several
+    TooManySelected(&'static str),
+    UnknownCommand(String),
+    InvalidPattern(String),
+    //A new name for an entry that isn't just a name, e.g. it has a '/' in it
+    InvalidName(String),
+    //Undo or redo was asked for with nothing left to undo or redo; holds which
+    NothingTo(&'static str),
+    //Undo or redo of the described operation was refused as an entry it touched is no longer
+    //as it was left, e.g. reason "has changed since"
+    Stale {
+        operation: &'static str,
+        description: String,
+        path: PathBuf,
+        reason: &'static str,
+    },
+    //Undo or redo of the described operation failed partway, with the first entry that failed
+    Partial {
+        operation: &'static str,
+        description: String,
+        path: PathBuf,
+        source: io::Error,
+    },
+    //Errors from code that reports them as text, such as the config and shell parsers
+    Other(String),
+}
+
+impl Error {
+    pub fn io(operation: &'static str, path: impl Into<PathBuf>, source: io::Error) -> Error {
+        Error::Io {
+            operation,
+            path: path.into(),
+            source,
+        }
+    }
+
+    //Mistakes in what was typed are warnings, anything that failed while being done is an error
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::Io { .. } | Error::Stale { .. } | Error::Partial { .. } | Error::Other(_) => {
+                Severity::Error
+            }
+            Error::Usage(_)
+            | Error::NothingSelected
+            | Error::TooManySelected(_)
+            | Error::UnknownCommand(_)
+            | Error::InvalidPattern(_)
+            | Error::InvalidName(_)
+            | Error::NothingTo(_) => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io {
+                operation,
+                path,
+                source,
+            } => write!(
+                f,
+                "Couldn't {} {}: {}",
+                operation,
+                file_ops::display_path(path),
+                source
+            ),
+            Error::Usage(usage) => write!(f, "Usage: {}", usage),
+            Error::NothingSelected => write!(f, "Nothing selected"),
+            Error::TooManySelected(operation) => {
+                write!(f, "Can't {} more than one entry at once", operation)
+            }
+            Error::UnknownCommand(name) => write!(f, "Not a command: {}", name),
+            Error::InvalidPattern(err) => write!(f, "Invalid pattern: {}", err),
+            Error::InvalidName(name) => write!(f, "Not a name: {} (names can't contain '/')", name),
+            Error::NothingTo(operation) => write!(f, "Nothing to {}", operation),
+            Error::Stale {
+                operation,
+                description,
+                path,
+                reason,
+            } => write!(
+                f,
+                "Can't {} {}: {} {}",
+                operation,
+                description,
+                file_ops::display_path(path),
+                reason
+            ),
+            Error::Partial {
+                operation,
+                description,
+                path,
+                source,
+            } => write!(
+                f,
+                "Couldn't fully {} {}: {}: {}",
+                operation,
+                description,
+                file_ops::display_path(path),
+                source
+            ),
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Partial { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use termion::event::Key;
+use termion::input::TermRead;
+
+use crate::watch;
+
+//Everything the main loop waits for
+pub enum Event {
+    Key(Key),
+    //The terminal changed size
+    Resize,
+    //A background worker finished something, such as a job or a preview
+    Wake,
+    //Entries changed in a watched directory
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    FileSystem(Vec<watch::Change>),
+    //The terminal hung up or failed, so no more keys will come
+    Quit,
+}
+
+//Set by the SIGWINCH handler, which can only do async-signal-safe things: set a flag and write
+//to the wake pipe
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+    let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        unsafe { libc::write(fd, b"r".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+//Sends events to the main loop from other threads, waking it if it's waiting
+#[derive(Clone)]
+pub struct Waker {
+    sender: Sender<Event>,
+    pipe: Arc<File>,
+}
+
+impl Waker {
+    pub fn send(&self, event: Event) {
+        if self.sender.send(event).is_ok() {
+            //The pipe doesn't block, and a full pipe already has the loop's attention
+            let _ = (&*self.pipe).write(b"w");
+        }
+    }
+
+    pub fn wake(&self) {
+        self.send(Event::Wake);
+    }
+}
+
+//Waits on the terminal and a wake pipe together, so the loop sleeps until something happens.
+//The terminal is only read here, on the main thread, so a program run in the foreground gets
+//every key typed while it runs.
+pub struct Events {
+    tty: File,
+    pipe: File,
+    receiver: Receiver<Event>,
+    waker: Waker,
+}
+
+//A waker, the pipe it writes to and the channel it sends on. Without a terminal, e.g. in tests,
+//events can be taken straight from the channel.
+pub fn channel() -> io::Result<(Waker, File, Receiver<Event>)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (pipe, pipe_writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    //pipe2 would set these at once, but it isn't on every platform
+    for fd in &fds {
+        let set = unsafe {
+            libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) == 0
+                && libc::fcntl(*fd, libc::F_SETFL, libc::O_NONBLOCK) == 0
+        };
+        if !set {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let waker = Waker {
+        sender,
+        pipe: Arc::new(pipe_writer),
+    };
+    Ok((waker, pipe, receiver))
+}
+
+impl Events {
+    pub fn new() -> io::Result<Events> {
+        let (waker, pipe, receiver) = channel()?;
+
+        SIGNAL_PIPE.store(waker.pipe.as_raw_fd(), Ordering::Relaxed);
+        let handler = on_resize as extern "C" fn(libc::c_int);
+        unsafe { libc::signal(libc::SIGWINCH, handler as libc::sighandler_t) };
+
+        Ok(Events {
+            tty: termion::get_tty()?,
+            pipe,
+            receiver,
+            waker,
+        })
+    }
+
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    //Waits up to `timeout` (forever if None) for something to happen, then returns everything
+    //that has. Nothing is returned if the time ran out.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<Event>> {
+        let mut poll_fds = [
+            poll_fd(self.tty.as_raw_fd()),
+            poll_fd(self.pipe.as_raw_fd()),
+        ];
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout.as_millis().min(i32::MAX as u128) as i32
+        });
+
+        let ready = unsafe { libc::poll(poll_fds.as_mut_ptr(), 2, timeout) };
+        if ready < 0 {
+            let err = io::Error::last_os_error();
+            //A signal such as SIGWINCH interrupted the wait, which is handled below
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+
+        let mut events = Vec::new();
+        //Otherwise poll would keep returning straight away and the loop would spin
+        let hung_up = poll_fds[0].revents & (libc::POLLHUP | libc::POLLERR) != 0;
+        if poll_fds[0].revents & libc::POLLIN != 0 {
+            //Up to a buffer's worth of keys; anything more is read on the next wait
+            let mut buffer = [0; 1024];
+            match self.tty.read(&mut buffer) {
+                Ok(0) => events.push(Event::Quit),
+                Ok(read) => events.extend(buffer[..read].keys().flatten().map(Event::Key)),
+                Err(_) if hung_up => events.push(Event::Quit),
+                Err(err) => return Err(err),
+            }
+        } else if hung_up {
+            events.push(Event::Quit);
+        }
+        if poll_fds[1].revents & libc::POLLIN != 0 {
+            let mut drained = [0; 256];
+            while matches!((&self.pipe).read(&mut drained), Ok(read) if read > 0) {}
+        }
+        if RESIZED.swap(false, Ordering::Relaxed) {
+            events.push(Event::Resize);
+        }
+        events.extend(self.receiver.try_iter());
+
+        Ok(events)
+    }
+}
+
+fn poll_fd(fd: RawFd) -> libc::pollfd {
+    libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    }
+}
@@ -1,49 +1,431 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, OsStr};
 use std::fs;
-use std::fs::{read_dir, File};
-use std::io::prelude::*;
-use std::path::PathBuf;
+use std::fs::read_dir;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use ignore::{Walk, WalkBuilder};
 
 use crate::app;
+use crate::error::Error;
+use crate::jobs::{JobResult, Progress};
+
+#[derive(Clone, PartialEq)]
+pub enum FileKind {
+    File,
+    Directory,
+    //`broken` is set when the target doesn't exist; `to_directory` when it's a directory
+    Symlink {
+        target: PathBuf,
+        broken: bool,
+        to_directory: bool,
+    },
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
 
-#[derive(Eq, PartialEq, PartialOrd, Ord, Clone)]
-pub enum DirectoryItem {
-    File((String, u64)),
-    Directory(String),
+//An entry in the file list, with its metadata as read when the list was loaded.
+//Everything describes the entry itself, so a symlink's size and times are the link's own.
+#[derive(Clone)]
+pub struct DirectoryItem {
+    path: PathBuf,
+    pub kind: FileKind,
+    pub size: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub user: String,
+    pub group: String,
+    pub modified: i64,
+    pub accessed: i64,
+    pub changed: i64,
+    pub inode: u64,
+    pub links: u64,
+}
+
+impl DirectoryItem {
+    pub fn from_path(path: &Path) -> Result<DirectoryItem, std::io::Error> {
+        let metadata = fs::symlink_metadata(path)?;
+        let file_type = metadata.file_type();
+
+        let kind = if file_type.is_symlink() {
+            let target = fs::read_link(path)?;
+            let target_metadata = fs::metadata(path);
+            FileKind::Symlink {
+                target,
+                broken: target_metadata.is_err(),
+                to_directory: target_metadata.map(|m| m.is_dir()).unwrap_or(false),
+            }
+        } else if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else if file_type.is_block_device() {
+            FileKind::BlockDevice
+        } else if file_type.is_char_device() {
+            FileKind::CharDevice
+        } else {
+            FileKind::File
+        };
+
+        Ok(DirectoryItem {
+            path: path.to_path_buf(),
+            kind,
+            size: metadata.len(),
+            mode: metadata.mode(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            user: user_name(metadata.uid()),
+            group: group_name(metadata.gid()),
+            modified: metadata.mtime(),
+            accessed: metadata.atime(),
+            changed: metadata.ctime(),
+            inode: metadata.ino(),
+            links: metadata.nlink(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn name(&self) -> &OsStr {
+        self.path
+            .file_name()
+            .unwrap_or_else(|| self.path.as_os_str())
+    }
+
+    //Dotfiles, which are hidden unless asked for
+    pub fn is_hidden(&self) -> bool {
+        self.name().as_bytes().first() == Some(&b'.')
+    }
+
+    //Everything known about the entry, one "Label: value" line each, for :info and previews
+    pub fn describe(&self) -> Vec<String> {
+        let kind = match &self.kind {
+            FileKind::File => String::from("regular file"),
+            FileKind::Directory => String::from("directory"),
+            FileKind::Symlink { target, broken, .. } => format!(
+                "symbolic link to {}{}",
+                display_path(target),
+                if *broken { " (broken)" } else { "" }
+            ),
+            FileKind::Fifo => String::from("named pipe"),
+            FileKind::Socket => String::from("socket"),
+            FileKind::BlockDevice => String::from("block device"),
+            FileKind::CharDevice => String::from("character device"),
+        };
+
+        vec![
+            format!("Path:        {}", display_path(self.path())),
+            format!("Type:        {}", kind),
+            format!(
+                "Size:        {} bytes ({})",
+                format_count(self.size),
+                format_size(self.size)
+            ),
+            format!(
+                "Permissions: {} ({:04o})",
+                self.permissions(),
+                self.mode & 0o7777
+            ),
+            format!("Owner:       {} ({})", self.user, self.uid),
+            format!("Group:       {} ({})", self.group, self.gid),
+            format!("Modified:    {}", format_timestamp(self.modified)),
+            format!("Accessed:    {}", format_timestamp(self.accessed)),
+            format!("Changed:     {}", format_timestamp(self.changed)),
+            format!("Inode:       {}", self.inode),
+            format!("Links:       {}", self.links),
+        ]
+    }
+
+    //True for directories and symlinks to directories, i.e. anything that can be opened
+    pub fn is_directory(&self) -> bool {
+        match self.kind {
+            FileKind::Directory => true,
+            FileKind::Symlink { to_directory, .. } => to_directory,
+            _ => false,
+        }
+    }
+
+    //Permissions in the form shown by `ls -l`, e.g. "drwxr-xr-x"
+    pub fn permissions(&self) -> String {
+        let kind = match self.kind {
+            FileKind::File => '-',
+            FileKind::Directory => 'd',
+            FileKind::Symlink { .. } => 'l',
+            FileKind::Fifo => 'p',
+            FileKind::Socket => 's',
+            FileKind::BlockDevice => 'b',
+            FileKind::CharDevice => 'c',
+        };
+
+        let mut permissions = String::new();
+        permissions.push(kind);
+        for (shift, special, set_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+            let bits = (self.mode >> shift) & 0o7;
+            let special = self.mode & special != 0;
+            permissions.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            permissions.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            permissions.push(match (bits & 0o1 != 0, special) {
+                (true, true) => set_char,
+                (false, true) => set_char.to_ascii_uppercase(),
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        permissions
+    }
+}
+
+//Human readable size, in the same 1000 based units as the file list
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < units.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{}{}", bytes, units[unit])
+    } else {
+        format!("{:.1}{}", size, units[unit])
+    }
+}
+
+//e.g. "1 file" or "1,204 files"
+pub fn format_quantity(count: u64, one: &str, many: &str) -> String {
+    format!(
+        "{} {}",
+        format_count(count),
+        if count == 1 { one } else { many }
+    )
+}
+
+//A count with thousands separators, e.g. "1,204"
+pub fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+//Local "YYYY-MM-DD hh:mm:ss" for a unix timestamp, for display
+pub fn format_timestamp(seconds: i64) -> String {
+    format_iso_timestamp(seconds).replacen('T', " ", 1)
+}
+
+//Local "YYYY-MM-DDThh:mm:ss" for a unix timestamp, as used in .trashinfo files
+pub fn format_iso_timestamp(seconds: i64) -> String {
+    let tm = local_time(seconds);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+fn local_time(seconds: i64) -> libc::tm {
+    let time = seconds as libc::time_t;
+    //tm is plain old data, so all zeroes is a valid value for localtime_r to fill in
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::localtime_r(&time, &mut tm);
+    }
+    tm
+}
+
+thread_local! {
+    //Looking names up reads /etc/passwd and friends, so each id is only looked up once
+    static USER_NAMES: RefCell<HashMap<u32, String>> = RefCell::new(HashMap::new());
+    static GROUP_NAMES: RefCell<HashMap<u32, String>> = RefCell::new(HashMap::new());
+}
+
+//Name of the user with the given id, or the id itself if it has no name
+pub fn user_name(uid: u32) -> String {
+    USER_NAMES.with(|names| {
+        names
+            .borrow_mut()
+            .entry(uid)
+            .or_insert_with(|| {
+                let mut buffer = vec![0 as libc::c_char; 4096];
+                let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+                let mut result: *mut libc::passwd = std::ptr::null_mut();
+                let status = unsafe {
+                    libc::getpwuid_r(
+                        uid,
+                        &mut passwd,
+                        buffer.as_mut_ptr(),
+                        buffer.len(),
+                        &mut result,
+                    )
+                };
+
+                if status == 0 && !result.is_null() {
+                    unsafe { CStr::from_ptr(passwd.pw_name) }
+                        .to_string_lossy()
+                        .into_owned()
+                } else {
+                    uid.to_string()
+                }
+            })
+            .clone()
+    })
+}
+
+//Name of the group with the given id, or the id itself if it has no name
+pub fn group_name(gid: u32) -> String {
+    GROUP_NAMES.with(|names| {
+        names
+            .borrow_mut()
+            .entry(gid)
+            .or_insert_with(|| {
+                let mut buffer = vec![0 as libc::c_char; 4096];
+                let mut group: libc::group = unsafe { std::mem::zeroed() };
+                let mut result: *mut libc::group = std::ptr::null_mut();
+                let status = unsafe {
+                    libc::getgrgid_r(
+                        gid,
+                        &mut group,
+                        buffer.as_mut_ptr(),
+                        buffer.len(),
+                        &mut result,
+                    )
+                };
+
+                if status == 0 && !result.is_null() {
+                    unsafe { CStr::from_ptr(group.gr_name) }
+                        .to_string_lossy()
+                        .into_owned()
+                } else {
+                    gid.to_string()
+                }
+            })
+            .clone()
+    })
+}
+
+//A file name as text for the UI. Bytes that aren't valid UTF-8 are shown as "\xNN" escapes,
+//and so are control characters, which would otherwise mess up the terminal.
+pub fn display_name(name: &OsStr) -> String {
+    let mut displayed = String::new();
+
+    for chunk in name.as_bytes().utf8_chunks() {
+        for character in chunk.valid().chars() {
+            if character.is_control() {
+                displayed.push_str(&format!("\\x{:02X}", character as u32));
+            } else {
+                displayed.push(character);
+            }
+        }
+        for byte in chunk.invalid() {
+            displayed.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+
+    displayed
+}
+
+//A whole path as text for the UI, escaped like `display_name`
+pub fn display_path(path: &Path) -> String {
+    display_name(path.as_os_str())
+}
+
+//The last component of `path` for the UI, or the whole path if it has none (e.g. "/")
+pub fn display_file_name(path: &Path) -> String {
+    display_name(path.file_name().unwrap_or(path.as_os_str()))
 }
 
 pub fn get_files_for_current_directory(
     app: &app::App,
 ) -> Result<Vec<DirectoryItem>, std::io::Error> {
-    //Get list, unwrap, and convert results to &Path
+    //Get list, and convert results to paths
     let dir_items: Vec<PathBuf> = match read_dir(app.current_directory.as_path()) {
-        Ok(val) => val.map(|f| f.unwrap().path()).collect(),
+        Ok(val) => val.flatten().map(|f| f.path()).collect(),
         Err(err) => return Err(err),
     };
 
-    //Convert items to DirectoryItem
-    let mut files: Vec<DirectoryItem> = Vec::new();
-    for item in dir_items {
-        let file = File::open(&item);
+    //Convert items to DirectoryItem, skipping any that vanish before they can be read
+    let files: Vec<DirectoryItem> = dir_items
+        .iter()
+        .filter_map(|item| DirectoryItem::from_path(item).ok())
+        .collect();
 
-        let file_size: u64 = match file {
-            Ok(file) => (file.metadata().unwrap().len() as f64 / 1000.00).ceil() as u64,
-            Err(_) => 0,
-        };
+    Ok(files)
+}
 
-        if item.is_file() {
-            let file = DirectoryItem::File((String::from(item.to_str().unwrap()), file_size));
-            files.push(file);
-        } else {
-            let file = DirectoryItem::Directory(String::from(item.to_str().unwrap()));
-            files.push(file);
-        }
-    }
+//Entries in `directory` that .gitignore, .ignore or git's exclude files say to leave out.
+//Ignore files in parent directories count too, as they do for git, and a .gitignore is
+//followed outside of git repositories as well.
+pub fn get_ignored_files(directory: &Path, files: &[DirectoryItem]) -> HashSet<PathBuf> {
+    let kept: HashSet<PathBuf> = WalkBuilder::new(directory)
+        .max_depth(Some(1))
+        .hidden(false)
+        .parents(true)
+        .require_git(false)
+        .build()
+        .flatten()
+        .map(|entry| entry.into_path())
+        .collect();
 
-    Ok(files)
+    files
+        .iter()
+        .map(|item| item.path().to_path_buf())
+        .filter(|path| !kept.contains(path))
+        .collect()
+}
+
+//Walks the tree under `root` the way the listing is set to show it: dotfiles and ignored files
+//are left out when hidden there. Git's own files are never what anyone is looking for, so
+//`.git` directories are always skipped.
+pub fn walk_tree(root: &Path, show_hidden: bool, respect_ignore_files: bool) -> Walk {
+    WalkBuilder::new(root)
+        .hidden(!show_hidden)
+        .parents(respect_ignore_files)
+        .ignore(respect_ignore_files)
+        .git_ignore(respect_ignore_files)
+        .git_global(respect_ignore_files)
+        .git_exclude(respect_ignore_files)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
 }
 
-pub fn rename_file(command: &Vec<String>, current_dir: &str, app: &app::App) -> Option<String> {
-    if command.len() > 1 && app.selection_index != None {
+//Renames the selected entry, returning its old and new paths
+pub fn rename_file(
+    command: &[String],
+    current_dir: &Path,
+    app: &app::App,
+) -> Result<(PathBuf, PathBuf), Error> {
+    let targets = app.get_target_paths();
+    if targets.len() > 1 {
+        return Err(Error::TooManySelected("rename"));
+    }
+    if targets.is_empty() {
+        return Err(Error::NothingSelected);
+    }
+
+    if command.len() > 1 {
         //put new file name back together after originally splitting on whitespace
         let new_name_split = &command[1..command.len()];
         let mut concat = String::new();
@@ -52,81 +434,198 @@ pub fn rename_file(command: &Vec<String>, current_dir: &str, app: &app::App) ->
         }
         let new_name = concat.trim_end();
 
-        let current_name = app.get_selected_file_path().unwrap();
+        //A path would move the entry somewhere else rather than rename it
+        if new_name.contains('/') {
+            return Err(Error::InvalidName(new_name.to_string()));
+        }
+
+        let current_name = targets[0].clone();
+        let new_path = current_dir.join(new_name);
 
-        match fs::rename(current_name, format!("{}/{}", current_dir, new_name)) {
-            Ok(_) => None,
-            Err(err) => Some(String::from(err.to_string())),
+        //rename() replaces what's there, which couldn't be undone. It may be the entry itself,
+        //e.g. when only the case changes on a case-insensitive filesystem.
+        if let (Ok(existing), Ok(current)) = (
+            fs::symlink_metadata(&new_path),
+            fs::symlink_metadata(&current_name),
+        ) {
+            if existing.dev() != current.dev() || existing.ino() != current.ino() {
+                return Err(Error::io(
+                    "rename",
+                    current_name,
+                    std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", display_path(&new_path)),
+                    ),
+                ));
+            }
+        }
+
+        match fs::rename(&current_name, &new_path) {
+            Ok(_) => Ok((current_name, new_path)),
+            Err(err) => Err(Error::io("rename", current_name, err)),
         }
     } else {
-        Some(String::from("Wrong number of arguments supplied"))
+        Err(Error::Usage(":rename <new name>"))
     }
 }
 
-pub fn delete_file(app: &app::App) -> Option<String> {
-    if app.selection_index != None {
-        let selection_index = app.selection_index.unwrap();
+#[derive(Default, Clone, Copy)]
+pub struct TreeSize {
+    pub directories: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
 
-        let result = match &app.directory_contents[selection_index] {
-            DirectoryItem::Directory(path) => fs::remove_dir_all(path),
-            DirectoryItem::File((path, _)) => fs::remove_file(path),
-        };
+impl TreeSize {
+    pub fn add(&mut self, other: TreeSize) {
+        self.directories += other.directories;
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
 
-        match result {
-            Ok(_) => None,
-            Err(err) => Some(String::from(err.to_string())),
+    //e.g. "3 directories, 1,204 files, 2.3GB" for confirmation prompts
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.directories > 0 {
+            parts.push(format_quantity(
+                self.directories,
+                "directory",
+                "directories",
+            ));
         }
-    } else {
-        Some(String::from("Nothing to delete"))
+        parts.push(format_quantity(self.files, "file", "files"));
+        parts.push(format_size(self.bytes));
+        parts.join(", ")
     }
 }
 
-pub fn read_file(app: &mut app::App) -> (Option<Vec<u8>>, Option<String>) {
-    let file_path = app.get_selected_file_path();
-    if let Some(path) = file_path {
-        //read the file
-        let mut file = File::open(&path).unwrap();
-        let mut buffer: Vec<u8> = Vec::new();
+//Counts everything under `path` without following symlinks. Unreadable parts are skipped.
+pub fn measure(path: &Path) -> TreeSize {
+    measure_until(path, &AtomicBool::new(false))
+}
 
-        //get old filename and store it
-        let split_path: Vec<String> = path.split("/").map(|s| s.to_string()).collect();
+//Like `measure`, but gives up part way through once `stop` is set
+pub fn measure_until(path: &Path, stop: &AtomicBool) -> TreeSize {
+    let mut size = TreeSize::default();
+    add_to_tree_size(path, &mut size, stop);
+    size
+}
 
-        let result = file.read_to_end(&mut buffer);
-        match result {
-            Ok(_) => (Some(buffer), Some(split_path.last().unwrap().to_string())),
-            Err(err) => {
-                app.error = Some(err.to_string());
-                (None, None)
+fn add_to_tree_size(path: &Path, size: &mut TreeSize, stop: &AtomicBool) {
+    if stop.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+
+    if metadata.is_dir() {
+        size.directories += 1;
+        if let Ok(entries) = read_dir(path) {
+            for entry in entries.flatten() {
+                add_to_tree_size(&entry.path(), size, stop);
             }
         }
     } else {
-        (None, None)
+        size.files += 1;
+        size.bytes += metadata.len();
     }
 }
 
-pub fn write_file(app: &mut app::App) -> Result<(), std::io::Error> {
-    let buffered_file = app.get_buffered_file();
-    if buffered_file != (None, None) {
-        let mut file = File::create(format!(
-            "{}/{}",
-            app.current_directory.to_str().unwrap(),
-            buffered_file.1.clone().unwrap().as_str()
-        ))?;
+//Removes a tree like `remove_path`, counting removed files and stopping when the job is cancelled.
+//Anything that can't be removed is added to `errors` and the rest of the tree is still removed.
+pub fn delete_with_progress(
+    path: &Path,
+    progress: &Progress,
+    errors: &mut Vec<(PathBuf, std::io::Error)>,
+) {
+    if progress.checkpoint().is_err() {
+        return;
+    }
 
-        let result = file.write(&buffered_file.0.unwrap());
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) => return record_error(errors, progress, path, err),
+    };
 
-        if let Err(err) = result {
-            app.error = Some(err.to_string());
-            Err(err)
-        } else {
-            Ok(())
+    let result = if metadata.is_dir() {
+        let errors_before = errors.len();
+        match read_dir(path) {
+            Ok(entries) => {
+                for entry in entries {
+                    match entry {
+                        Ok(entry) => delete_with_progress(&entry.path(), progress, errors),
+                        Err(err) => record_error(errors, progress, path, err),
+                    }
+                }
+            }
+            Err(err) => return record_error(errors, progress, path, err),
         }
+        //A directory that still has children can't be removed, and saying so again adds nothing
+        if errors.len() > errors_before || progress.is_cancelled() {
+            return;
+        }
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+
+    match result {
+        Ok(_) => progress.add_files(1),
+        Err(err) => record_error(errors, progress, path, err),
+    }
+}
+
+//Everything fails once a job is cancelled; the job reports that once instead
+fn record_error(
+    errors: &mut Vec<(PathBuf, std::io::Error)>,
+    progress: &Progress,
+    path: &Path,
+    err: std::io::Error,
+) {
+    if !progress.is_cancelled() {
+        errors.push((path.to_path_buf(), err));
+    }
+}
+
+//Removes a file, symlink or whole directory tree. Symlinks are never followed.
+pub fn remove_path(path: &Path) -> Result<(), std::io::Error> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
     } else {
-        Ok(())
+        fs::remove_file(path)
     }
 }
 
-pub fn create_directory(command: &Vec<String>, current_directory: &str) -> Option<String> {
+//Deletes every path on a job's worker thread
+pub fn delete_paths(paths: Vec<PathBuf>, progress: Arc<Progress>) -> JobResult {
+    let mut result = JobResult::default();
+
+    for path in &paths {
+        let size = measure(path);
+        progress.add_totals(size.files + size.directories, 0);
+    }
+
+    let mut errors = Vec::new();
+    for path in paths {
+        delete_with_progress(&path, &progress, &mut errors);
+        if fs::symlink_metadata(&path).is_ok() {
+            result.failed.push(path);
+        }
+    }
+    result.errors.extend(
+        errors
+            .into_iter()
+            .map(|(path, err)| Error::io("delete", path, err)),
+    );
+
+    result
+}
+
+//Creates a directory in the current directory, returning its path
+pub fn create_directory(command: &[String], current_directory: &Path) -> Result<PathBuf, Error> {
     if command.len() > 1 {
         //put new file name back together after originally splitting on whitespace
         let new_name_split = &command[1..command.len()];
@@ -136,13 +635,14 @@ pub fn create_directory(command: &Vec<String>, current_directory: &str) -> Optio
         }
         let new_name = concat.trim_end();
 
-        let result = fs::create_dir(String::from(format!("{}/{}", current_directory, new_name)));
+        let path = current_directory.join(new_name);
+        let result = fs::create_dir(&path);
 
         match result {
-            Ok(_) => None,
-            Err(err) => Some(err.to_string()),
+            Ok(_) => Ok(path),
+            Err(err) => Err(Error::io("create", path, err)),
         }
     } else {
-        Some(String::from("Wrong number of arguments supplied"))
+        Err(Error::Usage(":dir <name>"))
     }
 }
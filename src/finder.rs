@@ -0,0 +1,326 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::file_ops;
+
+//How many paths the walker collects before handing them over, so the UI isn't woken per file
+const BATCH_SIZE: usize = 256;
+//Only this many of the best matches are put in order and can be selected, which is far more
+//than fit on screen
+const MAX_SHOWN: usize = 1000;
+
+//Points for each matched character, and bonuses for where it's found. A run of consecutive
+//matches gets the boundary bonus of its first character throughout.
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 24;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_FILE_NAME: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+//A path found by the walker, relative to the finder's root
+pub struct Candidate {
+    pub path: PathBuf,
+    pub display: String,
+}
+
+pub struct Match {
+    pub candidate: usize,
+    pub score: i64,
+    //Character positions in the candidate's display string that the query matched
+    pub positions: Vec<usize>,
+}
+
+//Fuzzy search over every file under a directory. The tree is walked on a background thread
+//and results show up as they are found.
+pub struct Finder {
+    pub root: PathBuf,
+    pub query: String,
+    pub candidates: Vec<Candidate>,
+    //Every candidate that matches, with the best `MAX_SHOWN` first and in order
+    pub matches: Vec<Match>,
+    pub selected: usize,
+    pub walking: bool,
+    receiver: Receiver<Vec<PathBuf>>,
+}
+
+impl Finder {
+    pub fn new(root: PathBuf, show_hidden: bool, respect_ignore_files: bool) -> Finder {
+        let (sender, receiver) = mpsc::channel();
+        let walk_root = root.clone();
+        thread::spawn(move || walk(&walk_root, show_hidden, respect_ignore_files, sender));
+
+        Finder {
+            root,
+            query: String::new(),
+            candidates: Vec::new(),
+            matches: Vec::new(),
+            selected: 0,
+            walking: true,
+            receiver,
+        }
+    }
+
+    //Takes whatever the walker has found since the last call and matches it against the query
+    pub fn update(&mut self) {
+        let first_new = self.candidates.len();
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(paths) => self
+                    .candidates
+                    .extend(paths.into_iter().map(|path| Candidate {
+                        display: file_ops::display_path(&path),
+                        path,
+                    })),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.walking = false;
+                    break;
+                }
+            }
+        }
+
+        if self.candidates.len() > first_new {
+            let query: Vec<char> = self.query.chars().collect();
+            for index in first_new..self.candidates.len() {
+                if let Some(found) = self.score_candidate(&query, index) {
+                    self.matches.push(found);
+                }
+            }
+            self.order_matches();
+        }
+    }
+
+    //A longer query can only match what the shorter one did, so only those are checked again
+    pub fn push_char(&mut self, character: char) {
+        self.query.push(character);
+
+        let query: Vec<char> = self.query.chars().collect();
+        let previous = std::mem::take(&mut self.matches);
+        self.matches = previous
+            .iter()
+            .filter_map(|found| self.score_candidate(&query, found.candidate))
+            .collect();
+        self.selected = 0;
+        self.order_matches();
+    }
+
+    pub fn pop_char(&mut self) {
+        if self.query.pop().is_some() {
+            let query: Vec<char> = self.query.chars().collect();
+            self.matches = (0..self.candidates.len())
+                .filter_map(|index| self.score_candidate(&query, index))
+                .collect();
+            self.selected = 0;
+            self.order_matches();
+        }
+    }
+
+    pub fn move_selection(&mut self, down: bool) {
+        if down {
+            if self.selected + 1 < self.matches.len().min(MAX_SHOWN) {
+                self.selected += 1;
+            }
+        } else {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+
+    //Full path of the selected match
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.matches
+            .get(self.selected)
+            .map(|found| self.root.join(&self.candidates[found.candidate].path))
+    }
+
+    fn score_candidate(&self, query: &[char], index: usize) -> Option<Match> {
+        score(query, &self.candidates[index].display).map(|(score, positions)| Match {
+            candidate: index,
+            score,
+            positions,
+        })
+    }
+
+    //Puts the best matches first without sorting all of them, which matters for big trees
+    fn order_matches(&mut self) {
+        let candidates = &self.candidates;
+        let compare = |a: &Match, b: &Match| compare_matches(a, b, candidates);
+
+        if self.matches.len() > MAX_SHOWN {
+            self.matches.select_nth_unstable_by(MAX_SHOWN - 1, compare);
+        }
+        let shown = self.matches.len().min(MAX_SHOWN);
+        self.matches[..shown].sort_by(compare);
+        self.selected = self.selected.min(shown.saturating_sub(1));
+    }
+}
+
+//Best score first, then shorter paths, then alphabetically so the order doesn't jump around
+fn compare_matches(a: &Match, b: &Match, candidates: &[Candidate]) -> Ordering {
+    let (a_display, b_display) = (
+        &candidates[a.candidate].display,
+        &candidates[b.candidate].display,
+    );
+    b.score
+        .cmp(&a.score)
+        .then_with(|| a_display.len().cmp(&b_display.len()))
+        .then_with(|| a_display.cmp(b_display))
+}
+
+//Sends everything but directories under `root` in batches, until done or the finder is closed
+fn walk(
+    root: &Path,
+    show_hidden: bool,
+    respect_ignore_files: bool,
+    sender: mpsc::Sender<Vec<PathBuf>>,
+) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for entry in file_ops::walk_tree(root, show_hidden, respect_ignore_files).flatten() {
+        if entry.file_type().is_none_or(|kind| kind.is_dir()) {
+            continue;
+        }
+
+        if let Ok(path) = entry.path().strip_prefix(root) {
+            batch.push(path.to_path_buf());
+        }
+        if batch.len() == BATCH_SIZE {
+            let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+            //The finder was closed, so nobody wants the rest
+            if sender.send(full).is_err() {
+                return;
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        let _ = sender.send(batch);
+    }
+}
+
+//Scores `candidate` if every character of `query` appears in it in order, returning the score
+//and where the characters matched. Smart-case: case only matters if the query has uppercase.
+//The match is tried both from the start and from the file name, as matching in the file name
+//is usually what's wanted.
+pub fn score(query: &[char], candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let case_sensitive = query.iter().any(|character| character.is_uppercase());
+    let file_name_start = candidate
+        .iter()
+        .rposition(|character| *character == '/')
+        .map_or(0, |slash| slash + 1);
+
+    let from_start = match_from(query, &candidate, 0, case_sensitive)?;
+    let best = match match_from(query, &candidate, file_name_start, case_sensitive) {
+        Some(positions) if file_name_start > 0 => {
+            let in_name = score_positions(&positions, &candidate, file_name_start);
+            let anywhere = score_positions(&from_start, &candidate, file_name_start);
+            if in_name >= anywhere {
+                (in_name, positions)
+            } else {
+                (anywhere, from_start)
+            }
+        }
+        _ => (
+            score_positions(&from_start, &candidate, file_name_start),
+            from_start,
+        ),
+    };
+
+    Some(best)
+}
+
+//Finds the first place `query` matches from `start`, then walks back from its end to make the
+//match as tight as possible
+fn match_from(
+    query: &[char],
+    candidate: &[char],
+    start: usize,
+    case_sensitive: bool,
+) -> Option<Vec<usize>> {
+    let equal = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    let mut query_index = 0;
+    let mut end = None;
+    for (index, character) in candidate.iter().enumerate().skip(start) {
+        if equal(*character, query[query_index]) {
+            query_index += 1;
+            if query_index == query.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut query_index = query.len();
+    for index in (start..=end).rev() {
+        if query_index == 0 {
+            break;
+        }
+        if equal(candidate[index], query[query_index - 1]) {
+            query_index -= 1;
+            positions.push(index);
+        }
+    }
+    positions.reverse();
+
+    Some(positions)
+}
+
+fn score_positions(positions: &[usize], candidate: &[char], file_name_start: usize) -> i64 {
+    let mut score = 0;
+    let mut run_bonus = 0;
+
+    for (number, position) in positions.iter().enumerate() {
+        let mut bonus = if is_boundary(candidate, *position) {
+            BONUS_BOUNDARY
+        } else {
+            0
+        };
+
+        let gap = if number > 0 {
+            position - positions[number - 1] - 1
+        } else {
+            0
+        };
+        if number > 0 && gap == 0 {
+            bonus = bonus.max(run_bonus) + BONUS_CONSECUTIVE;
+        } else if gap > 0 {
+            score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (gap as i64 - 1);
+        }
+        run_bonus = bonus;
+
+        score += SCORE_MATCH + bonus;
+        if *position >= file_name_start {
+            score += BONUS_FILE_NAME;
+        }
+    }
+
+    score
+}
+
+//The start of the string, a word after a separator, or an uppercase letter in camelCase
+fn is_boundary(candidate: &[char], position: usize) -> bool {
+    if position == 0 {
+        return true;
+    }
+
+    let previous = candidate[position - 1];
+    let current = candidate[position];
+    matches!(previous, '/' | '_' | '-' | '.' | ' ')
+        || (previous.is_lowercase() && current.is_uppercase())
+}
@@ -0,0 +1,225 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::file_ops;
+use crate::pattern;
+
+//The search stops after this many matching lines
+pub const MAX_MATCHES: usize = 10_000;
+//Matched lines are cut to this many characters for the results list
+const MAX_LINE_LENGTH: usize = 300;
+
+pub struct GrepMatch {
+    //Relative to the directory the search started in
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub line: String,
+}
+
+//A content search over every file under a directory, run on a background thread. Matches
+//show up as they are found.
+pub struct Grep {
+    pub root: PathBuf,
+    pub pattern: String,
+    pub matches: Vec<GrepMatch>,
+    pub selected: usize,
+    pub searching: bool,
+    pub files_searched: Arc<AtomicUsize>,
+    receiver: Receiver<Vec<GrepMatch>>,
+    //Set to stop the worker before it opens another file
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Grep {
+    //Starts searching for the regular expression `pattern`, which is smart-case like `/`
+    pub fn new(
+        root: PathBuf,
+        pattern: &str,
+        show_hidden: bool,
+        respect_ignore_files: bool,
+    ) -> Result<Grep, String> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!pattern::has_uppercase(pattern))
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        let (sender, receiver) = mpsc::channel();
+        let files_searched = Arc::new(AtomicUsize::new(0));
+        let worker_root = root.clone();
+        let worker_files = Arc::clone(&files_searched);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            search(
+                &worker_root,
+                &regex,
+                show_hidden,
+                respect_ignore_files,
+                &worker_files,
+                &worker_cancelled,
+                sender,
+            )
+        });
+
+        Ok(Grep {
+            root,
+            pattern: pattern.to_string(),
+            matches: Vec::new(),
+            selected: 0,
+            searching: true,
+            files_searched,
+            receiver,
+            cancelled,
+        })
+    }
+
+    //Stops the search, keeping what it has found so far
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    //Takes whatever the search has found since the last call
+    pub fn update(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(matches) => self.matches.extend(matches),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.searching = false;
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn move_selection(&mut self, down: bool) {
+        if down {
+            if self.selected + 1 < self.matches.len() {
+                self.selected += 1;
+            }
+        } else {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+
+    //Full path and line number of the selected match
+    pub fn selected_match(&self) -> Option<(PathBuf, usize)> {
+        self.matches
+            .get(self.selected)
+            .map(|found| (self.root.join(&found.path), found.line_number))
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.matches.len() >= MAX_MATCHES
+    }
+}
+
+impl Drop for Grep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+//Sends the matches in each file as it's searched, until done, there are too many, or the
+//search is cancelled
+fn search(
+    root: &Path,
+    regex: &Regex,
+    show_hidden: bool,
+    respect_ignore_files: bool,
+    files_searched: &AtomicUsize,
+    cancelled: &AtomicBool,
+    sender: mpsc::Sender<Vec<GrepMatch>>,
+) {
+    let mut found = 0;
+
+    for entry in file_ops::walk_tree(root, show_hidden, respect_ignore_files).flatten() {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+
+        //Unreadable files are skipped like binary ones, as there's nowhere to list them
+        let lines = search_file(entry.path(), regex, MAX_MATCHES - found).unwrap_or_default();
+        files_searched.fetch_add(1, Ordering::Relaxed);
+        if lines.is_empty() {
+            continue;
+        }
+
+        let path = entry
+            .path()
+            .strip_prefix(root)
+            .unwrap_or(entry.path())
+            .to_path_buf();
+        found += lines.len();
+        let matches = lines
+            .into_iter()
+            .map(|(line_number, line)| GrepMatch {
+                path: path.clone(),
+                line_number,
+                line,
+            })
+            .collect();
+
+        if sender.send(matches).is_err() || found >= MAX_MATCHES {
+            return;
+        }
+    }
+}
+
+//Line numbers and text of up to `limit` lines matching `regex`. Files with a NUL byte are
+//taken to be binary and give no matches, as grep does.
+fn search_file(path: &Path, regex: &Regex, limit: usize) -> io::Result<Vec<(usize, String)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    //Most binary files show it in their first block, so they aren't read line by line at all
+    if reader.fill_buf()?.contains(&0) {
+        return Ok(Vec::new());
+    }
+
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut line_number = 0;
+
+    while lines.len() < limit {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+
+        if line.contains(&0) {
+            return Ok(Vec::new());
+        }
+        if regex.is_match(&line) {
+            lines.push((line_number, display_line(&line)));
+        }
+    }
+
+    Ok(lines)
+}
+
+//The line as it can be shown in the list: decoded, without the line ending, with tabs and other
+//control characters turned into spaces, and cut to a reasonable length
+fn display_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line)
+        .trim_end_matches(['\n', '\r'])
+        .chars()
+        .map(|character| {
+            if character.is_control() {
+                ' '
+            } else {
+                character
+            }
+        })
+        .take(MAX_LINE_LENGTH)
+        .collect()
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use tui::style::{Color, Modifier, Style};
+
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+//Text longer than this isn't highlighted, as a long minified line can take seconds
+const MAX_BYTES: usize = 32 * 1024;
+
+//A run of text in one style
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+//Names of the themes that can be set with the `theme` option
+pub fn theme_names() -> Vec<String> {
+    ThemeSet::load_defaults().themes.into_keys().collect()
+}
+
+//Syntax definitions and a colour theme. Loading them takes a moment, so it's done once, on the
+//preview thread.
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    pub fn new(theme: &str) -> Highlighter {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let theme = themes
+            .remove(theme)
+            .or_else(|| themes.remove(DEFAULT_THEME))
+            .unwrap_or_default();
+
+        Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        }
+    }
+
+    //Highlights `lines` from the start of `path`, going by its name, extension or shebang.
+    //Gives nothing if the language isn't known or there's too much text.
+    pub fn highlight(&self, path: &Path, lines: &[String]) -> Option<Vec<Vec<Span>>> {
+        if lines.iter().map(|line| line.len() + 1).sum::<usize>() > MAX_BYTES {
+            return None;
+        }
+
+        let name = path.file_name()?.to_str()?;
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_extension(name)
+            .or_else(|| {
+                extension.and_then(|extension| self.syntaxes.find_syntax_by_extension(extension))
+            })
+            .or_else(|| {
+                lines
+                    .first()
+                    .and_then(|line| self.syntaxes.find_syntax_by_first_line(line))
+            })?;
+        if syntax.name == "Plain Text" {
+            return None;
+        }
+
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let mut highlighted = Vec::with_capacity(lines.len());
+        for line in lines {
+            //The syntaxes expect each line to end with its newline
+            let line = format!("{}\n", line);
+            let regions = highlighter.highlight_line(&line, &self.syntaxes).ok()?;
+            highlighted.push(
+                regions
+                    .into_iter()
+                    .map(|(style, text)| Span {
+                        text: text.trim_end_matches('\n').to_string(),
+                        style: to_tui_style(style),
+                    })
+                    .filter(|span| !span.text.is_empty())
+                    .collect(),
+            );
+        }
+
+        Some(highlighted)
+    }
+}
+
+//Only the foreground is used, so the terminal's own background shows through
+fn to_tui_style(style: syntect::highlighting::Style) -> Style {
+    let colour = style.foreground;
+    let mut modifier = Modifier::empty();
+    if style.font_style.contains(FontStyle::BOLD) {
+        modifier |= Modifier::BOLD;
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        modifier |= Modifier::ITALIC;
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        modifier |= Modifier::UNDERLINED;
+    }
+
+    Style::default()
+        .fg(Color::Rgb(colour.r, colour.g, colour.b))
+        .modifier(modifier)
+}
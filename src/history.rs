@@ -0,0 +1,163 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::config;
+
+//How many commands are kept, the oldest being forgotten first
+pub const MAX_HISTORY: usize = 1000;
+
+//$XDG_DATA_HOME/tfex/history
+pub fn history_path() -> io::Result<PathBuf> {
+    Ok(config::data_home()?.join("tfex/history"))
+}
+
+//Commands run in earlier sessions and this one, oldest first. Each is added to the file as it's
+//run, so sessions running at the same time don't lose each other's commands.
+#[derive(Default)]
+pub struct History {
+    pub entries: Vec<String>,
+    //The file they're kept in, or None to only keep them for this session
+    path: Option<PathBuf>,
+    //The entry being shown while going through them with Up and Down
+    position: Option<usize>,
+    //What had been typed before going through them, for Down to come back to
+    draft: String,
+}
+
+//Ctrl-r: the text being looked for and the newest entry containing it
+pub struct HistorySearch {
+    pub query: String,
+    pub found: Option<usize>,
+}
+
+impl History {
+    //Reads the history from `path`. A missing file is an empty history.
+    pub fn load(path: PathBuf) -> io::Result<History> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut history = History {
+            entries: contents
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+            path: Some(path),
+            ..History::default()
+        };
+
+        //Only trimmed on loading, so the file isn't rewritten for every command
+        if history.entries.len() > MAX_HISTORY {
+            let excess = history.entries.len() - MAX_HISTORY;
+            history.entries.drain(..excess);
+            history.rewrite()?;
+        }
+        Ok(history)
+    }
+
+    //Records a command that was run, unless it's the same as the one before
+    pub fn add(&mut self, command: &str) -> io::Result<()> {
+        self.reset();
+        if command.trim().is_empty() || self.entries.last().is_some_and(|last| last == command) {
+            return Ok(());
+        }
+
+        self.entries.push(command.to_string());
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+
+        match &self.path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", command)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn rewrite(&self) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            let mut contents = self.entries.join("\n");
+            contents.push('\n');
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+
+    //Stops going through the entries, e.g. when the command box is left
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+    }
+
+    //The entry before the one shown, remembering `current` if going back from the line being
+    //typed. None if there's nothing older.
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            Some(0) => return None,
+            Some(position) => position - 1,
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+        };
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    //The entry after the one shown, or the line that was being typed after the newest
+    pub fn next(&mut self) -> Option<String> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(self.entries[position + 1].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    //The newest entry containing `query`, looking back from before `before`
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+}
+
+impl HistorySearch {
+    pub fn new() -> HistorySearch {
+        HistorySearch {
+            query: String::new(),
+            found: None,
+        }
+    }
+
+    //Looks again from the newest entry, as the query has changed
+    pub fn update(&mut self, history: &History) {
+        self.found = history.search(&self.query, history.entries.len());
+    }
+
+    //Ctrl-r again: the next older entry containing the query, staying put if there isn't one
+    pub fn older(&mut self, history: &History) {
+        let before = self.found.unwrap_or(history.entries.len());
+        if let Some(found) = history.search(&self.query, before) {
+            self.found = Some(found);
+        }
+    }
+
+    pub fn matched<'a>(&self, history: &'a History) -> Option<&'a str> {
+        self.found
+            .and_then(|found| history.entries.get(found))
+            .map(String::as_str)
+    }
+}
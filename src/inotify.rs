@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::File;
+use std::io::{self, PipeReader, PipeWriter, Read, Write};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::events::{Event, Waker};
+use crate::watch::{Change, ChangeKind};
+
+//Events that change a directory's entries or what is shown about them
+const MASK: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_MODIFY
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_ATTRIB
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF
+    | libc::IN_ONLYDIR;
+//Events are collected for this long before being sent, so a burst of them, or a file being
+//written, makes a few updates rather than one per event
+const BATCH_TIME: Duration = Duration::from_millis(100);
+
+//Filesystems where changes can be made without the kernel seeing them, so inotify misses them:
+//network and FUSE filesystems, and the kernel's own virtual ones
+const UNWATCHABLE_FILESYSTEMS: &[i64] = &[
+    0x6969,     //NFS
+    0x517b,     //SMB
+    0xff534d42, //CIFS
+    0xfe534d42, //SMB2
+    0x65735546, //FUSE
+    0x01021997, //9P
+    0x9fa0,     //proc
+    0x62656572, //sysfs
+];
+
+//Watches the directory being listed and the one being previewed, sending their changes to the
+//main loop as Event::FileSystem
+pub struct Watcher {
+    pub inotify: Arc<File>,
+    //Directories by watch descriptor, shared with the reading thread
+    directories: Arc<Mutex<HashMap<i32, PathBuf>>>,
+    listed: Option<i32>,
+    previewed: Option<i32>,
+    //Written to when the watcher is dropped, to stop the reading thread
+    stop: PipeWriter,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    pub fn new(waker: Waker) -> io::Result<Watcher> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let inotify = Arc::new(unsafe { File::from_raw_fd(fd) });
+        let directories = Arc::new(Mutex::new(HashMap::new()));
+        let (stopped, stop) = io::pipe()?;
+
+        let reader_inotify = Arc::clone(&inotify);
+        let reader_directories = Arc::clone(&directories);
+        let reader = thread::spawn(move || {
+            read_events(&reader_inotify, &stopped, &reader_directories, &waker)
+        });
+
+        Ok(Watcher {
+            inotify,
+            directories,
+            listed: None,
+            previewed: None,
+            stop,
+            reader: Some(reader),
+        })
+    }
+
+    //Watches the listed directory instead of the last one. False if its changes can't be seen
+    //this way, so it has to be read again now and then instead.
+    pub fn watch_listed(&mut self, directory: &Path) -> bool {
+        let watch = self.replace(self.listed, self.previewed, directory);
+        self.listed = watch;
+        watch.is_some()
+    }
+
+    //Watches the directory being previewed, if any
+    pub fn watch_previewed(&mut self, directory: Option<&Path>) {
+        self.previewed = match directory {
+            Some(directory) => self.replace(self.previewed, self.listed, directory),
+            None => {
+                self.remove(self.previewed, self.listed);
+                None
+            }
+        };
+    }
+
+    //Swaps the watch `old` for one on `directory`. `other` is the other watch, which may be on
+    //the same directory, so the kernel gives it the same descriptor and it mustn't be removed.
+    fn replace(&mut self, old: Option<i32>, other: Option<i32>, directory: &Path) -> Option<i32> {
+        let already_watched = old.is_some_and(|old| {
+            self.directories.lock().is_ok_and(|directories| {
+                directories.get(&old).is_some_and(|path| path == directory)
+            })
+        });
+        if already_watched {
+            return old;
+        }
+
+        self.remove(old, other);
+        if !is_watchable(directory) {
+            return None;
+        }
+        let path = CString::new(directory.as_os_str().as_bytes()).ok()?;
+        let watch =
+            unsafe { libc::inotify_add_watch(self.inotify.as_raw_fd(), path.as_ptr(), MASK) };
+        if watch < 0 {
+            return None;
+        }
+
+        if let Ok(mut directories) = self.directories.lock() {
+            directories.insert(watch, directory.to_path_buf());
+        }
+        Some(watch)
+    }
+
+    fn remove(&mut self, watch: Option<i32>, other: Option<i32>) {
+        if let Some(watch) = watch.filter(|watch| Some(*watch) != other) {
+            unsafe { libc::inotify_rm_watch(self.inotify.as_raw_fd(), watch) };
+            if let Ok(mut directories) = self.directories.lock() {
+                directories.remove(&watch);
+            }
+        }
+    }
+}
+
+//Stops the reading thread, which closes the inotify fd along with the watches
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let _ = self.stop.write_all(&[0]);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn is_watchable(directory: &Path) -> bool {
+    let path = match CString::new(directory.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    let mut stats: libc::statfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stats) } != 0 {
+        return false;
+    }
+    !UNWATCHABLE_FILESYSTEMS.contains(&(stats.f_type as i64))
+}
+
+//Reads events until the watcher is dropped, sending them in batches
+fn read_events(
+    inotify: &File,
+    stopped: &PipeReader,
+    directories: &Mutex<HashMap<i32, PathBuf>>,
+    waker: &Waker,
+) {
+    //Room for plenty of events, aligned for inotify_event
+    let mut buffer = vec![0u64; 4096];
+
+    loop {
+        let mut raw = Vec::new();
+        //Block for the first events, then take whatever else comes in the batch's time
+        let mut started: Option<Instant> = None;
+        loop {
+            let timeout = match started {
+                None => -1,
+                Some(started) => BATCH_TIME.saturating_sub(started.elapsed()).as_millis() as i32,
+            };
+            if started.is_some() && timeout == 0 {
+                break;
+            }
+            match wait_readable(inotify, stopped, timeout) {
+                Some(true) => {}
+                Some(false) => break,
+                None => return,
+            }
+
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, buffer.len() * 8)
+            };
+            match (&*inotify).read(bytes) {
+                Ok(read) if read > 0 => raw.extend(parse(&bytes[..read])),
+                _ => return,
+            }
+            started.get_or_insert_with(Instant::now);
+        }
+
+        let changes = match directories.lock() {
+            Ok(directories) => to_changes(raw, &directories),
+            Err(_) => return,
+        };
+        if !changes.is_empty() {
+            waker.send(Event::FileSystem(changes));
+        }
+    }
+}
+
+//Whether there are events to read within `timeout` milliseconds, or None once `stopped` has
+//been written to or closed
+fn wait_readable(inotify: &File, stopped: &PipeReader, timeout: i32) -> Option<bool> {
+    let mut poll_fds = [inotify.as_raw_fd(), stopped.as_raw_fd()].map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    });
+    loop {
+        let ready = unsafe { libc::poll(poll_fds.as_mut_ptr(), 2, timeout) };
+        if ready >= 0 {
+            if poll_fds[1].revents != 0 {
+                return None;
+            }
+            return Some(ready > 0);
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return Some(false);
+        }
+    }
+}
+
+struct RawEvent {
+    watch: i32,
+    mask: u32,
+    cookie: u32,
+    name: OsString,
+}
+
+fn parse(bytes: &[u8]) -> Vec<RawEvent> {
+    let header = mem::size_of::<libc::inotify_event>();
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    while offset + header <= bytes.len() {
+        let event: libc::inotify_event =
+            unsafe { std::ptr::read_unaligned(bytes[offset..].as_ptr() as *const _) };
+        let name_end = (offset + header + event.len as usize).min(bytes.len());
+        //The name is padded with NUL bytes
+        let name = &bytes[offset + header..name_end];
+        let name = &name[..name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len())];
+
+        events.push(RawEvent {
+            watch: event.wd,
+            mask: event.mask,
+            cookie: event.cookie,
+            name: OsStr::from_bytes(name).to_os_string(),
+        });
+        offset = name_end;
+    }
+
+    events
+}
+
+//Pairs the two halves of renames within a directory, and sends each other entry once
+fn to_changes(events: Vec<RawEvent>, directories: &HashMap<i32, PathBuf>) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+
+    for (index, event) in events.iter().enumerate() {
+        if event.mask & libc::IN_Q_OVERFLOW != 0 {
+            //Events were lost, in any of the directories
+            changes.extend(directories.values().map(|directory| Change {
+                directory: directory.clone(),
+                name: OsString::new(),
+                kind: ChangeKind::Rescan,
+            }));
+            continue;
+        }
+
+        let directory = match directories.get(&event.watch) {
+            Some(directory) => directory,
+            //A watch that has just been removed
+            None => continue,
+        };
+        let change = |name: &OsStr, kind| Change {
+            directory: directory.clone(),
+            name: name.to_os_string(),
+            kind,
+        };
+
+        if event.mask & (libc::IN_DELETE_SELF | libc::IN_MOVE_SELF) != 0 {
+            changes.push(change(OsStr::new(""), ChangeKind::Rescan));
+        } else if event.mask & libc::IN_MOVED_FROM != 0 {
+            let moved_to = events[index + 1..].iter().find(|other| {
+                other.mask & libc::IN_MOVED_TO != 0
+                    && other.cookie == event.cookie
+                    && other.watch == event.watch
+            });
+            match moved_to {
+                Some(moved_to) => changes.push(change(
+                    &moved_to.name,
+                    ChangeKind::Renamed(event.name.clone()),
+                )),
+                None => changes.push(change(&event.name, ChangeKind::Changed)),
+            }
+        } else if event.mask & libc::IN_MOVED_TO != 0 {
+            let paired = events[..index].iter().any(|other| {
+                other.mask & libc::IN_MOVED_FROM != 0
+                    && other.cookie == event.cookie
+                    && other.watch == event.watch
+            });
+            if !paired {
+                changes.push(change(&event.name, ChangeKind::Changed));
+            }
+        } else if !event.name.is_empty() {
+            let seen = changes.iter().any(|seen| {
+                matches!(seen.kind, ChangeKind::Changed)
+                    && seen.name == event.name
+                    && seen.directory == *directory
+            });
+            if !seen {
+                changes.push(change(&event.name, ChangeKind::Changed));
+            }
+        }
+    }
+
+    changes
+}
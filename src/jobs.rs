@@ -0,0 +1,239 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::events::Waker;
+
+//Counters shared between a worker thread and the UI, plus the flags used to control the worker
+#[derive(Default)]
+pub struct Progress {
+    pub bytes_done: AtomicU64,
+    pub bytes_total: AtomicU64,
+    pub files_done: AtomicU64,
+    pub files_total: AtomicU64,
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl Progress {
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_files(&self, files: u64) {
+        self.files_done.fetch_add(files, Ordering::Relaxed);
+    }
+
+    pub fn add_totals(&self, files: u64, bytes: u64) {
+        self.files_total.fetch_add(files, Ordering::Relaxed);
+        self.bytes_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    //Called by workers between chunks: blocks while paused, and fails once cancelled
+    pub fn checkpoint(&self) -> io::Result<()> {
+        while self.is_paused() && !self.is_cancelled() {
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        if self.is_cancelled() {
+            Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobKind {
+    Copy,
+    Move,
+    Delete,
+    Trash,
+    Shell,
+}
+
+impl JobKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            JobKind::Copy => "Copy",
+            JobKind::Move => "Move",
+            JobKind::Delete => "Delete",
+            JobKind::Trash => "Trash",
+            JobKind::Shell => "Shell",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct JobResult {
+    pub errors: Vec<Error>,
+    //Sources that were not processed, so a failed move can stay on the clipboard
+    pub failed: Vec<PathBuf>,
+    //Sources and where they ended up, for operations that can be undone
+    pub done: Vec<(PathBuf, PathBuf)>,
+}
+
+impl JobResult {
+    //A job that failed before it could start on anything
+    pub fn error(err: Error) -> JobResult {
+        JobResult {
+            errors: vec![err],
+            ..JobResult::default()
+        }
+    }
+}
+
+pub struct Job {
+    pub id: usize,
+    pub kind: JobKind,
+    pub description: String,
+    pub progress: Arc<Progress>,
+    pub started: Instant,
+    handle: Option<thread::JoinHandle<JobResult>>,
+}
+
+impl Job {
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .map(|handle| handle.is_finished())
+            .unwrap_or(true)
+    }
+
+    //0 to 100, going by whichever of bytes and files is further behind
+    pub fn percent(&self) -> u16 {
+        (self.fraction() * 100.0) as u16
+    }
+
+    //Estimated time left, from the average rate so far
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if fraction <= 0.0 || fraction >= 1.0 || elapsed < 1.0 || self.progress.is_paused() {
+            return None;
+        }
+
+        Some(Duration::from_secs_f64(
+            elapsed * (1.0 - fraction) / fraction,
+        ))
+    }
+
+    fn fraction(&self) -> f64 {
+        let ratio = |done: &AtomicU64, total: &AtomicU64| {
+            let total = total.load(Ordering::Relaxed);
+            if total == 0 {
+                None
+            } else {
+                Some(done.load(Ordering::Relaxed).min(total) as f64 / total as f64)
+            }
+        };
+
+        let progress = &self.progress;
+        match (
+            ratio(&progress.bytes_done, &progress.bytes_total),
+            ratio(&progress.files_done, &progress.files_total),
+        ) {
+            (Some(bytes), Some(files)) => bytes.min(files),
+            (Some(fraction), None) | (None, Some(fraction)) => fraction,
+            (None, None) => 0.0,
+        }
+    }
+}
+
+pub struct JobManager {
+    pub jobs: Vec<Job>,
+    next_id: usize,
+    //Wakes the main loop when a job finishes
+    waker: Waker,
+}
+
+impl JobManager {
+    pub fn new(waker: Waker) -> JobManager {
+        JobManager {
+            jobs: Vec::new(),
+            next_id: 0,
+            waker,
+        }
+    }
+
+    //Runs `work` on a worker thread and returns the new job's id
+    pub fn spawn<F>(&mut self, kind: JobKind, description: String, work: F) -> usize
+    where
+        F: FnOnce(Arc<Progress>) -> JobResult + Send + 'static,
+    {
+        self.next_id += 1;
+        let progress = Arc::new(Progress::default());
+        let worker_progress = Arc::clone(&progress);
+        let waker = self.waker.clone();
+        let handle = thread::spawn(move || {
+            let result = work(worker_progress);
+            waker.wake();
+            result
+        });
+
+        self.jobs.push(Job {
+            id: self.next_id,
+            kind,
+            description,
+            progress,
+            started: Instant::now(),
+            handle: Some(handle),
+        });
+
+        self.next_id
+    }
+
+    //The id the next job spawned will get
+    pub fn next_id(&self) -> usize {
+        self.next_id + 1
+    }
+
+    //Removes finished jobs and returns them with their results
+    pub fn take_finished(&mut self) -> Vec<(Job, JobResult)> {
+        let mut finished = Vec::new();
+        let mut index = 0;
+
+        while index < self.jobs.len() {
+            if self.jobs[index].is_finished() {
+                let mut job = self.jobs.remove(index);
+                let result = match job.handle.take().map(|handle| handle.join()) {
+                    Some(Ok(result)) => result,
+                    _ => JobResult::error(Error::Other(String::from("worker thread panicked"))),
+                };
+                finished.push((job, result));
+            } else {
+                index += 1;
+            }
+        }
+
+        finished
+    }
+
+    //The job with the given id, or the most recent one
+    pub fn find(&self, id: Option<usize>) -> Option<&Job> {
+        match id {
+            Some(id) => self.jobs.iter().find(|job| job.id == id),
+            None => self.jobs.last(),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::app::describe_paths;
+use crate::copy;
+use crate::error::Error;
+use crate::file_ops;
+use crate::jobs::Progress;
+use crate::trash::Trash;
+
+//How many operations are remembered for undo
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Rename,
+    CreateDirectory,
+    Move,
+    Trash,
+}
+
+//One path an operation moved from `from` to `to`. The device and inode of wherever the item
+//currently is are remembered, so undo and redo can tell if something else has been put there.
+pub struct Step {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    identity: Option<(u64, u64)>,
+}
+
+impl Step {
+    pub fn new(from: PathBuf, to: PathBuf) -> Step {
+        let identity = identity(&to);
+        Step { from, to, identity }
+    }
+
+    //For directory creation, where there is nothing to move
+    pub fn created(path: PathBuf) -> Step {
+        Step::new(path.clone(), path)
+    }
+}
+
+pub struct Operation {
+    pub kind: Kind,
+    pub steps: Vec<Step>,
+}
+
+impl Operation {
+    pub fn new(kind: Kind, steps: Vec<Step>) -> Operation {
+        Operation { kind, steps }
+    }
+
+    pub fn describe(&self) -> String {
+        let sources: Vec<PathBuf> = self.steps.iter().map(|step| step.from.clone()).collect();
+        let destinations: Vec<PathBuf> = self.steps.iter().map(|step| step.to.clone()).collect();
+
+        match self.kind {
+            Kind::Rename => format!(
+                "rename {} to {}",
+                describe_paths(&sources),
+                describe_paths(&destinations)
+            ),
+            Kind::CreateDirectory => format!("create directory {}", describe_paths(&destinations)),
+            Kind::Move => format!(
+                "move {} to {}",
+                describe_paths(&sources),
+                self.steps
+                    .first()
+                    .and_then(|step| step.to.parent())
+                    .map(file_ops::display_path)
+                    .unwrap_or_default()
+            ),
+            Kind::Trash => format!("trash {}", describe_paths(&sources)),
+        }
+    }
+
+    //Makes sure every item is still where the operation left it before anything is touched.
+    //Fails with the entry that isn't and why.
+    fn check_undo(&self) -> Result<(), (&Path, &'static str)> {
+        for step in &self.steps {
+            check_unchanged(&step.to, step.identity)?;
+
+            if self.kind == Kind::CreateDirectory {
+                let empty = fs::read_dir(&step.to)
+                    .map(|mut entries| entries.next().is_none())
+                    .unwrap_or(false);
+                if !empty {
+                    return Err((&step.to, "is not empty"));
+                }
+            } else {
+                check_free(&step.from)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    //Makes sure every item is still where the undo put it back
+    fn check_redo(&self) -> Result<(), (&Path, &'static str)> {
+        for step in &self.steps {
+            match self.kind {
+                Kind::CreateDirectory => check_free(&step.to)?,
+                //Trashing again picks a new name in the trash, so only the original matters
+                Kind::Trash => check_unchanged(&step.from, step.identity)?,
+                Kind::Rename | Kind::Move => {
+                    check_unchanged(&step.from, step.identity)?;
+                    check_free(&step.to)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//Operations that can be undone, and undone operations that can be redone
+#[derive(Default)]
+pub struct Journal {
+    pub undo: Vec<Operation>,
+    pub redo: Vec<Operation>,
+}
+
+impl Journal {
+    //Remembers a new operation. Anything that was undone can no longer be redone.
+    pub fn record(&mut self, mut operation: Operation) {
+        //Renaming something to its own name does nothing, so there's nothing to undo
+        if operation.kind != Kind::CreateDirectory {
+            operation.steps.retain(|step| step.from != step.to);
+        }
+        if operation.steps.is_empty() {
+            return;
+        }
+
+        self.undo.push(operation);
+        if self.undo.len() > MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    //Undoes the last operation, returning a message saying what was undone
+    pub fn undo(&mut self) -> Result<String, Error> {
+        let operation = self.undo.pop().ok_or(Error::NothingTo("undo"))?;
+        let description = operation.describe();
+
+        if let Err((path, reason)) = operation.check_undo() {
+            let err = Error::Stale {
+                operation: "undo",
+                description,
+                path: path.to_path_buf(),
+                reason,
+            };
+            self.undo.push(operation);
+            return Err(err);
+        }
+
+        let (done, failed) = apply(operation, undo_step);
+        self.redo.extend(done);
+        match failed {
+            Some((operation, path, source)) => {
+                self.undo.push(operation);
+                Err(Error::Partial {
+                    operation: "undo",
+                    description,
+                    path,
+                    source,
+                })
+            }
+            None => Ok(format!("Undid {}", description)),
+        }
+    }
+
+    //Redoes the last undone operation, returning a message saying what was redone
+    pub fn redo(&mut self) -> Result<String, Error> {
+        let operation = self.redo.pop().ok_or(Error::NothingTo("redo"))?;
+        let description = operation.describe();
+
+        if let Err((path, reason)) = operation.check_redo() {
+            let err = Error::Stale {
+                operation: "redo",
+                description,
+                path: path.to_path_buf(),
+                reason,
+            };
+            self.redo.push(operation);
+            return Err(err);
+        }
+
+        let (done, failed) = apply(operation, redo_step);
+        self.undo.extend(done);
+        match failed {
+            Some((operation, path, source)) => {
+                self.redo.push(operation);
+                Err(Error::Partial {
+                    operation: "redo",
+                    description,
+                    path,
+                    source,
+                })
+            }
+            None => Ok(format!("Redid {}", description)),
+        }
+    }
+}
+
+fn undo_step(kind: Kind, step: &mut Step) -> io::Result<()> {
+    match kind {
+        Kind::Rename | Kind::Move => move_item(&step.to, &step.from)?,
+        Kind::CreateDirectory => fs::remove_dir(&step.to)?,
+        Kind::Trash => {
+            Trash::containing(&step.to)?.restore(step.to.file_name().unwrap_or_default())?;
+        }
+    }
+
+    step.identity = identity(&step.from);
+    Ok(())
+}
+
+fn redo_step(kind: Kind, step: &mut Step) -> io::Result<()> {
+    match kind {
+        Kind::Rename | Kind::Move => move_item(&step.from, &step.to)?,
+        Kind::CreateDirectory => fs::create_dir(&step.to)?,
+        //Back into the trash it was restored from
+        Kind::Trash => {
+            step.to = Trash::containing(&step.to)?
+                .trash_path(&step.from, &Arc::new(Progress::default()))?
+        }
+    }
+
+    step.identity = identity(&step.to);
+    Ok(())
+}
+
+//Runs `step_fn` on every step. Returns the steps that worked and, if any failed, an operation
+//holding just those steps with the first error and its entry, so they can be tried again later.
+fn apply(
+    operation: Operation,
+    step_fn: fn(Kind, &mut Step) -> io::Result<()>,
+) -> (Option<Operation>, Option<(Operation, PathBuf, io::Error)>) {
+    let kind = operation.kind;
+    let mut done = Vec::new();
+    let mut failed = Vec::new();
+    let mut error = None;
+
+    for mut step in operation.steps {
+        match step_fn(kind, &mut step) {
+            Ok(_) => done.push(step),
+            Err(err) => {
+                if error.is_none() {
+                    error = Some((step.from.clone(), err));
+                }
+                failed.push(step);
+            }
+        }
+    }
+
+    let done = if done.is_empty() {
+        None
+    } else {
+        Some(Operation::new(kind, done))
+    };
+    (
+        done,
+        error.map(|(path, err)| (Operation::new(kind, failed), path, err)),
+    )
+}
+
+//Moves an item back or forth, renaming when possible
+fn move_item(source: &Path, destination: &Path) -> io::Result<()> {
+    let mut report = copy::CopyReport::default();
+    copy::move_path(source, destination, false, &mut report);
+    match report.errors.into_iter().next() {
+        Some((_, err)) => Err(err),
+        None => Ok(()),
+    }
+}
+
+fn check_unchanged(path: &Path, expected: Option<(u64, u64)>) -> Result<(), (&Path, &'static str)> {
+    let current = identity(path);
+    if current.is_none() {
+        Err((path, "no longer exists"))
+    } else if current != expected {
+        Err((path, "has changed since"))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_free(path: &Path) -> Result<(), (&Path, &'static str)> {
+    if fs::symlink_metadata(path).is_ok() {
+        Err((path, "already exists"))
+    } else {
+        Ok(())
+    }
+}
+
+fn identity(path: &Path) -> Option<(u64, u64)> {
+    fs::symlink_metadata(path)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
@@ -0,0 +1,198 @@
+use std::io;
+
+use termion::event::Key;
+
+use crate::app::{self, App};
+use crate::paste::Resolution;
+
+//Handles a key in whichever mode the app is in. Returns false to quit.
+pub fn handle_key(app: &mut App, key: Key) -> Result<bool, io::Error> {
+    if app.mode == app::Mode::Browse {
+        match key {
+            Key::Char('q') => return Ok(false),
+            Key::Char('j') => app.move_selection_down(),
+            Key::Char('k') => app.move_selection_up(),
+            Key::Char('h') => app.move_selection_left(),
+            Key::Char('l') => app.move_selection_right(),
+            Key::Char('\n') => app.open_folder(),
+            Key::Char(':') => app.start_command(),
+            Key::Char('/') => {
+                app.start_search();
+                return Ok(true);
+            }
+            Key::Char('f') => app.open_finder(),
+            Key::Char('n') => app.jump_to_match(true),
+            Key::Char('N') => app.jump_to_match(false),
+            Key::Backspace => app.move_up_directory(),
+            Key::Char('c') => app.copy_selected_to_clipboard(),
+            Key::Char('x') => app.cut_selected_to_clipboard(),
+            Key::Char('v') => app.paste_clipboard(),
+            Key::Char(' ') => app.toggle_mark(),
+            Key::Char('V') => app.start_visual_selection(),
+            Key::Char('A') => app.mark_all(),
+            Key::Char('I') => app.invert_marks(),
+            Key::Esc => {
+                app.clear_marks();
+                app.clear_search();
+            }
+            Key::Char('s') => app.cycle_sort_key(),
+            Key::Char('S') => app.reverse_sort(),
+            Key::Char('.') => app.toggle_hidden(),
+            Key::Char('p') => app.toggle_preview(),
+            Key::Char('u') => app.undo(),
+            Key::Ctrl('r') => app.redo(),
+            _ => {}
+        }
+    } else if app.mode == app::Mode::Select {
+        match key {
+            Key::Char('j') => app.move_selection_down(),
+            Key::Char('k') => app.move_selection_up(),
+            Key::Char('h') => app.move_selection_left(),
+            Key::Char('l') => app.move_selection_right(),
+            Key::Char('V') | Key::Char(' ') => app.commit_visual_selection(),
+            Key::Char(':') => {
+                app.commit_visual_selection();
+                app.start_command();
+            }
+            Key::Char('c') => app.copy_selected_to_clipboard(),
+            Key::Char('x') => app.cut_selected_to_clipboard(),
+            Key::Esc => app.cancel_visual_selection(),
+            _ => {}
+        }
+    } else if app.mode == app::Mode::Command {
+        if app.history_search.is_some() {
+            handle_history_search_key(app, key);
+        } else {
+            handle_command_key(app, key);
+        }
+        //The listing narrows as a :filter is typed
+        if app.mode == app::Mode::Command {
+            app.apply_filters();
+        }
+        //The command may have opened a dialog or view, which mustn't see the Enter that ran it
+        return Ok(true);
+    } else if app.mode == app::Mode::Find {
+        if let Some(finder) = &mut app.finder {
+            match key {
+                Key::Up | Key::Ctrl('p') => finder.move_selection(false),
+                Key::Down | Key::Ctrl('n') => finder.move_selection(true),
+                Key::Backspace => finder.pop_char(),
+                Key::Char('\n') => app.accept_finder(),
+                Key::Char(chr) => finder.push_char(chr),
+                Key::Esc => app.close_finder(),
+                _ => {}
+            }
+        }
+    }
+
+    if app.mode == app::Mode::Conflict {
+        let (resolution, apply_to_all) = match key {
+            Key::Char('o') => (Some(Resolution::Overwrite), false),
+            Key::Char('O') => (Some(Resolution::Overwrite), true),
+            Key::Char('s') => (Some(Resolution::Skip), false),
+            Key::Char('S') => (Some(Resolution::Skip), true),
+            Key::Char('r') => (Some(Resolution::Rename), false),
+            Key::Char('R') => (Some(Resolution::Rename), true),
+            Key::Char('n') => (Some(Resolution::OverwriteIfNewer), false),
+            Key::Char('N') => (Some(Resolution::OverwriteIfNewer), true),
+            _ => (None, false),
+        };
+
+        if let Some(resolution) = resolution {
+            app.resolve_conflict(resolution, apply_to_all);
+        } else if key == Key::Esc {
+            app.cancel_paste();
+        }
+    }
+
+    if app.mode == app::Mode::View {
+        match key {
+            Key::Char('j') | Key::Down => app.scroll_text(true),
+            Key::Char('k') | Key::Up => app.scroll_text(false),
+            Key::Char('q') | Key::Esc => app.close_text(),
+            _ => {}
+        }
+    }
+
+    if app.mode == app::Mode::Confirm {
+        match key {
+            Key::Char('y') | Key::Char('Y') | Key::Char('\n') => app.accept_confirmation(),
+            Key::Char('n') | Key::Char('N') | Key::Esc => app.reject_confirmation(),
+            _ => {}
+        }
+    }
+
+    if app.mode == app::Mode::Grep {
+        if let Some(grep) = &mut app.grep {
+            match key {
+                Key::Char('j') | Key::Down => grep.move_selection(true),
+                Key::Char('k') | Key::Up => grep.move_selection(false),
+                Key::Char('\n') => app.grep_go_to(),
+                Key::Char('e') => app.grep_edit(),
+                Key::Char('q') | Key::Esc => app.close_grep(),
+                _ => {}
+            }
+        }
+    }
+
+    if app.mode == app::Mode::Search {
+        match key {
+            Key::Char('\n') => app.accept_search(),
+            Key::Char(chr) => app.add_to_search(chr),
+            Key::Backspace => app.search_backspace(),
+            Key::Esc => app.cancel_search(),
+            _ => {}
+        }
+    }
+
+    Ok(true)
+}
+
+//Editing keys follow readline's emacs mode
+fn handle_command_key(app: &mut App, key: Key) {
+    let line = &mut app.command_line;
+    match key {
+        Key::Char('\n') => app.execute_command(),
+        Key::Char(chr) => line.insert(chr),
+        //Deleting the last character leaves the command box, like Esc
+        Key::Backspace | Key::Ctrl('h') if line.is_empty() => app.cancel_command(),
+        Key::Backspace | Key::Ctrl('h') => line.delete_before(),
+        Key::Delete | Key::Ctrl('d') => line.delete_under(),
+        Key::Left | Key::Ctrl('b') => line.move_left(),
+        Key::Right | Key::Ctrl('f') => line.move_right(),
+        Key::Home | Key::Ctrl('a') => line.move_home(),
+        Key::End | Key::Ctrl('e') => line.move_end(),
+        Key::Alt('b') => line.move_word_left(),
+        Key::Alt('f') => line.move_word_right(),
+        Key::Ctrl('w') => line.kill_word_before(),
+        Key::Alt('\x7f') => line.kill_alphanumeric_word_before(),
+        Key::Alt('d') => line.kill_word_after(),
+        Key::Ctrl('u') => line.kill_to_start(),
+        Key::Ctrl('k') => line.kill_to_end(),
+        Key::Ctrl('y') => line.yank(),
+        Key::Up | Key::Ctrl('p') => app.previous_command(),
+        Key::Down | Key::Ctrl('n') => app.next_command(),
+        Key::Ctrl('r') => app.start_history_search(),
+        Key::Esc => app.cancel_command(),
+        _ => {}
+    }
+}
+
+//Ctrl-r: typing narrows the search, Ctrl-r again finds an older match
+fn handle_history_search_key(app: &mut App, key: Key) {
+    match key {
+        Key::Char('\n') => {
+            app.accept_history_search();
+            app.execute_command();
+        }
+        Key::Char(chr) => app.add_to_history_search(chr),
+        Key::Backspace | Key::Ctrl('h') => app.history_search_backspace(),
+        Key::Ctrl('r') => app.search_history_older(),
+        Key::Esc | Key::Ctrl('g') => app.cancel_history_search(),
+        //Anything else takes the match to edit and then edits it
+        _ => {
+            app.accept_history_search();
+            handle_command_key(app, key);
+        }
+    }
+}
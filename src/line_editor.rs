@@ -0,0 +1,136 @@
+//A single line of text being edited, with a cursor and the last text that was killed, like
+//readline's emacs mode. The cursor is an index into the characters, from 0 to their length.
+#[derive(Default)]
+pub struct LineEditor {
+    text: Vec<char>,
+    pub cursor: usize,
+    //What Ctrl-w, Ctrl-u, Ctrl-k and Alt-d last removed, for Ctrl-y to put back
+    killed: String,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    //Replaces the line, putting the cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.set_text("");
+    }
+
+    pub fn insert(&mut self, character: char) {
+        self.text.insert(self.cursor, character);
+        self.cursor += 1;
+    }
+
+    pub fn delete_before(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn delete_under(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.len());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.word_start(char::is_alphanumeric);
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self.word_end(char::is_alphanumeric);
+    }
+
+    //Ctrl-w: back to the previous space, so a whole path goes at once
+    pub fn kill_word_before(&mut self) {
+        let start = self.word_start(|character| !character.is_whitespace());
+        self.kill(start, self.cursor);
+    }
+
+    //Alt-Backspace: back to the start of the word, stopping at punctuation such as '/'
+    pub fn kill_alphanumeric_word_before(&mut self) {
+        let start = self.word_start(char::is_alphanumeric);
+        self.kill(start, self.cursor);
+    }
+
+    //Alt-d: forward to the end of the word
+    pub fn kill_word_after(&mut self) {
+        let end = self.word_end(char::is_alphanumeric);
+        self.kill(self.cursor, end);
+    }
+
+    pub fn kill_to_start(&mut self) {
+        self.kill(0, self.cursor);
+    }
+
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor, self.text.len());
+    }
+
+    //Puts the last killed text back at the cursor
+    pub fn yank(&mut self) {
+        let killed: Vec<char> = self.killed.chars().collect();
+        let count = killed.len();
+        self.text.splice(self.cursor..self.cursor, killed);
+        self.cursor += count;
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.killed = self.text.drain(start..end).collect();
+            self.cursor = start;
+        }
+    }
+
+    //Where the word before the cursor starts, skipping characters that aren't part of one first
+    fn word_start(&self, in_word: fn(char) -> bool) -> usize {
+        let mut index = self.cursor;
+        while index > 0 && !in_word(self.text[index - 1]) {
+            index -= 1;
+        }
+        while index > 0 && in_word(self.text[index - 1]) {
+            index -= 1;
+        }
+        index
+    }
+
+    //Where the word after the cursor ends, skipping characters that aren't part of one first
+    fn word_end(&self, in_word: fn(char) -> bool) -> usize {
+        let mut index = self.cursor;
+        while index < self.text.len() && !in_word(self.text[index]) {
+            index += 1;
+        }
+        while index < self.text.len() && in_word(self.text[index]) {
+            index += 1;
+        }
+        index
+    }
+}
@@ -1,81 +1,148 @@
-use std::{io, thread, time};
+use std::{env, io, process};
 
-use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 
 use tui::backend::TermionBackend;
 use tui::Terminal;
 
 mod app;
+mod args;
 mod commands;
+mod config;
+mod copy;
+mod error;
+mod events;
 mod file_ops;
+mod finder;
+mod grep;
+mod highlight;
+mod history;
+#[cfg(target_os = "linux")]
+mod inotify;
+mod jobs;
+mod journal;
+mod keys;
+mod line_editor;
+mod messages;
+mod opener;
+mod paste;
+mod pattern;
+mod preview;
+mod shell;
+mod sort;
+#[cfg(test)]
+mod tests;
+mod trash;
+mod tty;
 mod ui;
+mod watch;
 
 use app::App;
 
 fn main() -> Result<(), io::Error> {
-    //Initialize terminal
-    let stdout = io::stdout().into_raw_mode()?;
-    let backend = TermionBackend::new(stdout);
+    //Parse command line arguments before touching the terminal so errors print normally
+    let args = match args::parse(env::args_os().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("tfex: {}\nTry 'tfex --help' for more information.", err);
+            process::exit(2);
+        }
+    };
+
+    if args.help {
+        println!("{}", args::USAGE);
+        return Ok(());
+    }
+
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("tfex: {}", err);
+            process::exit(2);
+        }
+    };
+
+    //Initialize terminal. Raw mode is kept apart from the backend so it can be suspended
+    //while another program uses the terminal.
+    let raw_mode = io::stdout().into_raw_mode()?;
+    let backend = TermionBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     terminal.clear()?;
 
-    //Initialize input
-    let mut stdin = termion::async_stdin().keys();
+    //Initialize input, resize and background worker events
+    let mut events = events::Events::new()?;
 
     //Initialize App state
-    let mut app = App::new(&mut terminal);
-
-    //Main application loop
-    loop {
-        app.update_window_height();
-        //Handle input
-        let input = stdin.next();
-        if let Some(Ok(key)) = input {
-            if app.mode == app::Mode::Browse {
-                match key {
-                    termion::event::Key::Char('q') => break,
-                    termion::event::Key::Char('j') => app.move_selection_down(),
-                    termion::event::Key::Char('k') => app.move_selection_up(),
-                    termion::event::Key::Char('h') => app.move_selection_left(),
-                    termion::event::Key::Char('l') => app.move_selection_right(),
-                    termion::event::Key::Char('\n') => app.open_folder(),
-                    termion::event::Key::Char(':') => app.change_mode(app::Mode::Command),
-                    termion::event::Key::Backspace => app.move_up_directory()?,
-                    termion::event::Key::Char('c') => app.load_selected_into_file_buffer(),
-                    termion::event::Key::Char('x') => {
-                        app.load_selected_into_file_buffer();
-                        file_ops::delete_file(&app);
-                    }
-                    termion::event::Key::Char('v') => app.write_buffered_file(),
-                    _ => {}
-                }
-            }
+    let mut app = match App::new(
+        args.directory.clone(),
+        args.select,
+        config,
+        events.waker(),
+        terminal.size()?.height,
+    ) {
+        Ok(app) => app,
+        Err(err) => {
+            terminal.clear()?;
+            drop(terminal);
+            eprintln!(
+                "tfex: cannot open {}: {}",
+                file_ops::display_path(&args.directory),
+                err
+            );
+            process::exit(1);
+        }
+    };
 
-            if app.mode == app::Mode::Command {
-                if let termion::event::Key::Char(chr) = key {
-                    if chr != '\n' {
-                        app.add_to_command_buffer(chr);
-                    } else {
-                        app.execute_command();
+    //Commands typed in earlier sessions, for Up and Ctrl-r in the command box. The app starts
+    //with an empty history so tests don't share the user's.
+    match history::history_path().and_then(history::History::load) {
+        Ok(history) => app.history = history,
+        Err(err) => app
+            .messages
+            .warn(format!("Couldn't read the command history: {}", err)),
+    }
+
+    //Without XDG_DATA_HOME or HOME there's no trash, which is reported when one is needed
+    app.trash = trash::Trash::home().ok();
+
+    //Main application loop. It sleeps until a key, a resize or a background worker wakes it, or
+    //until the app has a timer running, and only redraws when something may have changed.
+    ui::draw(&mut terminal, &mut app)?;
+    'main: loop {
+        let mut changed = false;
+        for event in events.wait(app.next_timeout())? {
+            changed = true;
+            match event {
+                events::Event::Key(key) => {
+                    if !keys::handle_key(&mut app, key)? {
+                        break 'main;
                     }
                 }
-                if key == termion::event::Key::Esc {
-                    app.change_mode(app::Mode::Browse);
-                    app.command_buffer = Vec::new();
-                }
-                if key == termion::event::Key::Backspace {
-                    if app.command_buffer.len() > 1 {
-                        app.command_buffer.truncate(app.command_buffer.len() - 1);
-                    }
+                events::Event::FileSystem(changes) => app.apply_changes(changes)?,
+                events::Event::Resize => app.set_terminal_height(terminal.size()?.height),
+                events::Event::Wake => {}
+                events::Event::Quit => {
+                    //There's no terminal left to restore, and restoring it would panic
+                    std::mem::forget(raw_mode);
+                    return Ok(());
                 }
             }
         }
 
-        app.populate_files()?;
-        ui::draw(&mut app)?;
-        thread::sleep(time::Duration::from_millis(50));
+        if let Some(mut command) = app.foreground_command.take() {
+            if let Err(err) = tty::run_foreground(&mut terminal, &raw_mode, &mut command) {
+                app.messages.error(err);
+            }
+            //Whatever ran may have changed the files
+            app.refresh()?;
+            changed = true;
+        }
+
+        if app.update()? || changed {
+            app.update_window_height();
+            ui::draw(&mut terminal, &mut app)?;
+        }
     }
     Ok(())
 }
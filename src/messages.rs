@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::file_ops;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+
+    //How long a message stays on screen; errors get longer to be read
+    fn duration(self) -> Duration {
+        match self {
+            Severity::Info => Duration::from_secs(2),
+            Severity::Warning => Duration::from_secs(3),
+            Severity::Error => Duration::from_secs(5),
+        }
+    }
+}
+
+pub struct Message {
+    pub severity: Severity,
+    pub text: String,
+    //Unix time it was reported, for :messages
+    pub time: i64,
+}
+
+//Everything reported this session. The newest message is shown in place of the command box
+//until it has been on screen for its severity's duration.
+#[derive(Default)]
+pub struct Messages {
+    pub history: Vec<Message>,
+    //Whether the newest message is still to be shown
+    showing: bool,
+    //When the newest message was first drawn
+    shown_since: Option<Instant>,
+}
+
+impl Messages {
+    pub fn push(&mut self, severity: Severity, text: impl Into<String>) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs() as i64);
+        self.history.push(Message {
+            severity,
+            text: text.into(),
+            time,
+        });
+        self.showing = true;
+        self.shown_since = None;
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Severity::Info, text);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.push(Severity::Warning, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Severity::Error, text);
+    }
+
+    pub fn report(&mut self, err: Error) {
+        self.push(err.severity(), err.to_string());
+    }
+
+    //The message on screen, if there is one
+    pub fn current(&self) -> Option<&Message> {
+        if self.showing {
+            self.history.last()
+        } else {
+            None
+        }
+    }
+
+    //Called when the message is drawn, to start timing how long it's been shown
+    pub fn drawn(&mut self) {
+        if self.showing && self.shown_since.is_none() {
+            self.shown_since = Some(Instant::now());
+        }
+    }
+
+    //How long until the message on screen should be cleared
+    pub fn time_left(&self) -> Option<Duration> {
+        let since = self.shown_since?;
+        let severity = self.current()?.severity;
+        Some(severity.duration().saturating_sub(since.elapsed()))
+    }
+
+    //Clears the message once it has been shown for long enough. True if it was cleared.
+    pub fn expire(&mut self) -> bool {
+        if self.time_left() == Some(Duration::ZERO) {
+            self.showing = false;
+            self.shown_since = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    //One line per message, oldest first
+    pub fn describe(&self) -> Vec<String> {
+        if self.history.is_empty() {
+            return vec![String::from("No messages")];
+        }
+
+        self.history
+            .iter()
+            .map(|message| {
+                format!(
+                    "{} {:<7} {}",
+                    &file_ops::format_timestamp(message.time)[11..],
+                    message.severity.name(),
+                    message.text
+                )
+            })
+            .collect()
+    }
+}
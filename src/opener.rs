@@ -0,0 +1,231 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::preview;
+
+//How much of a file is read to guess its type
+const SNIFF_BYTES: u64 = 512;
+
+//Signatures at the start of common binary formats
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+];
+
+enum Matcher {
+    //Lowercase, without the dot
+    Extensions(Vec<String>),
+    Mime(GlobMatcher),
+    Glob(GlobMatcher),
+}
+
+//An `open <kind> <pattern> = <command>` line from the config file
+pub struct Rule {
+    matcher: Matcher,
+    command: Vec<String>,
+    background: bool,
+}
+
+//Parses the part of the key after "open" and the command. The kinds are:
+//  ext pdf,djvu      extensions, ignoring case
+//  mime image/*      the type guessed from the file's contents
+//  glob *.log        the file name
+//A command ending in '&' is started in the background without the terminal, for graphical
+//programs. "%f" in the command is replaced by the file, which is added at the end otherwise.
+pub fn parse_rule(key: &str, command: &str) -> Result<Rule, String> {
+    let mut words = key.split_whitespace();
+    let (kind, pattern) = match (words.next(), words.next(), words.next()) {
+        (Some(kind), Some(pattern), None) => (kind, pattern),
+        _ => return Err(String::from("expected 'open <ext|mime|glob> <pattern>'")),
+    };
+
+    let matcher = match kind {
+        "ext" => Matcher::Extensions(
+            pattern
+                .split(',')
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+        ),
+        "mime" => Matcher::Mime(compile_glob(pattern)?),
+        "glob" => Matcher::Glob(compile_glob(pattern)?),
+        _ => {
+            return Err(format!(
+                "unknown opener kind '{}', expected ext, mime or glob",
+                kind
+            ))
+        }
+    };
+
+    let mut command: Vec<String> = command.split_whitespace().map(String::from).collect();
+    let background = command.last().is_some_and(|word| word == "&");
+    if background {
+        command.pop();
+    }
+    if command.is_empty() {
+        return Err(String::from("expected a command to open with"));
+    }
+
+    Ok(Rule {
+        matcher,
+        command,
+        background,
+    })
+}
+
+fn compile_glob(pattern: &str) -> Result<GlobMatcher, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|err| err.kind().to_string())
+}
+
+//How to open a file. Background programs get no terminal and aren't waited for.
+pub struct Opener {
+    pub command: Command,
+    pub background: bool,
+}
+
+//The first rule that matches `path`, falling back to $EDITOR for text and xdg-open otherwise
+pub fn opener_for(path: &Path, rules: &[Rule]) -> Opener {
+    let name = path.file_name().unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    //Only read the file if a rule needs its type
+    let mut mime = None;
+
+    for rule in rules {
+        let matches = match &rule.matcher {
+            Matcher::Extensions(extensions) => extension
+                .as_ref()
+                .is_some_and(|extension| extensions.contains(extension)),
+            Matcher::Mime(glob) => glob.is_match(mime.get_or_insert_with(|| sniff_mime(path))),
+            Matcher::Glob(glob) => glob.is_match(name),
+        };
+
+        if matches {
+            let mut command = Command::new(&rule.command[0]);
+            let mut placeholder_used = false;
+            for word in &rule.command[1..] {
+                if word.contains("%f") {
+                    placeholder_used = true;
+                    //Built as an OsString so names that aren't UTF-8 survive
+                    let mut argument = OsString::new();
+                    for (index, part) in word.split("%f").enumerate() {
+                        if index > 0 {
+                            argument.push(path);
+                        }
+                        argument.push(part);
+                    }
+                    command.arg(argument);
+                } else {
+                    command.arg(word);
+                }
+            }
+            if !placeholder_used {
+                command.arg(path);
+            }
+
+            return Opener {
+                command,
+                background: rule.background,
+            };
+        }
+    }
+
+    let mime = mime.unwrap_or_else(|| sniff_mime(path));
+    if mime.starts_with("text/") {
+        Opener {
+            command: editor_command(path, None),
+            background: false,
+        }
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        Opener {
+            command,
+            background: true,
+        }
+    }
+}
+
+//Guesses a file's MIME type from its first bytes: a known signature, or text/plain for UTF-8
+//without NUL bytes. Anything else is application/octet-stream.
+pub fn sniff_mime(path: &Path) -> String {
+    let mut bytes = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(SNIFF_BYTES).read_to_end(&mut bytes);
+    }
+
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return mime.to_string();
+    }
+    if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return String::from("image/webp");
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return String::from("video/mp4");
+    }
+
+    if preview::is_text(&bytes) {
+        String::from("text/plain")
+    } else {
+        String::from("application/octet-stream")
+    }
+}
+
+//$EDITOR (vi if it isn't set) opening `path`, at `line_number` if given. Most editors take
+//"+N" for the line to start on.
+pub fn editor_command(path: &Path, line_number: Option<usize>) -> Command {
+    let editor = env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"));
+    //The variable may hold arguments too, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+
+    let mut command = Command::new(words.next().unwrap_or("vi"));
+    command.args(words);
+    if let Some(line_number) = line_number {
+        command.arg(format!("+{}", line_number));
+    }
+    command.arg(path);
+    command
+}
+
+//Starts a program without the terminal, so it can't draw over the TUI or read its keys. It's
+//waited for on another thread so it doesn't linger as a zombie.
+pub fn spawn_background(mut command: Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Couldn't run {}: {}", program, err))?;
+
+    thread::spawn(move || child.wait());
+    Ok(())
+}
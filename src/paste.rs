@@ -0,0 +1,187 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::app::Clipboard;
+use crate::copy;
+use crate::error::Error;
+use crate::file_ops;
+use crate::jobs::{JobResult, Progress};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Overwrite,
+    Skip,
+    Rename,
+    OverwriteIfNewer,
+}
+
+pub struct PasteItem {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub conflict: bool,
+    pub resolution: Option<Resolution>,
+}
+
+//A paste waiting for its conflicts to be resolved before anything is written
+pub struct PastePlan {
+    pub items: Vec<PasteItem>,
+    pub cut: bool,
+}
+
+impl PastePlan {
+    pub fn new(clipboard: &Clipboard, destination_dir: &Path) -> PastePlan {
+        let items = clipboard
+            .paths
+            .iter()
+            .filter_map(|source| {
+                let destination = destination_dir.join(source.file_name()?);
+
+                //Cutting into the same directory is a no-op, but a copy onto itself is a conflict
+                let exists = fs::symlink_metadata(&destination).is_ok();
+                let conflict = exists && !(clipboard.cut && *source == destination);
+
+                Some(PasteItem {
+                    source: source.clone(),
+                    destination,
+                    conflict,
+                    resolution: None,
+                })
+            })
+            .collect();
+
+        PastePlan {
+            items,
+            cut: clipboard.cut,
+        }
+    }
+
+    pub fn conflict_count(&self) -> usize {
+        self.items.iter().filter(|item| item.conflict).count()
+    }
+
+    //Index of the next conflict that still needs a resolution, for the dialog
+    pub fn next_conflict(&self) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| item.conflict && item.resolution.is_none())
+    }
+
+    pub fn resolve(&mut self, resolution: Resolution, apply_to_all: bool) {
+        for item in self.items.iter_mut() {
+            if item.conflict && item.resolution.is_none() {
+                item.resolution = Some(resolution);
+                if !apply_to_all {
+                    break;
+                }
+            }
+        }
+    }
+
+    //Performs the paste on a job's worker thread
+    pub fn execute(self, progress: Arc<Progress>) -> JobResult {
+        let mut result = JobResult::default();
+
+        //Settle every destination first so the job's totals are known before copying starts
+        let mut work: Vec<(PathBuf, PathBuf, bool)> = Vec::new();
+        for item in self.items {
+            let mut destination = item.destination;
+            let mut overwrite = false;
+
+            if item.conflict {
+                match item.resolution {
+                    Some(Resolution::Overwrite) => overwrite = true,
+                    Some(Resolution::Rename) => destination = unique_destination(&destination),
+                    Some(Resolution::OverwriteIfNewer) => {
+                        if !is_newer(&item.source, &destination) {
+                            continue;
+                        }
+                        overwrite = true;
+                    }
+                    Some(Resolution::Skip) | None => continue,
+                }
+            }
+
+            let size = file_ops::measure(&item.source);
+            progress.add_totals(size.files, size.bytes);
+            work.push((item.source, destination, overwrite));
+        }
+
+        for (source, destination, overwrite) in work {
+            if progress.is_cancelled() {
+                result.failed.push(source);
+                continue;
+            }
+
+            let mut report = copy::CopyReport::new(Arc::clone(&progress));
+            if self.cut {
+                copy::move_path(&source, &destination, overwrite, &mut report);
+            } else {
+                if overwrite {
+                    if let Err(err) = copy::prepare_overwrite(&source, &destination) {
+                        report.errors.push((destination.clone(), err));
+                    }
+                }
+                if report.errors.is_empty() {
+                    copy::copy_path(&source, &destination, &mut report);
+                }
+            }
+
+            if !report.errors.is_empty() {
+                let operation = if self.cut { "move" } else { "copy" };
+                result.errors.extend(
+                    report
+                        .errors
+                        .into_iter()
+                        .map(|(path, err)| Error::io(operation, path, err)),
+                );
+                result.failed.push(source);
+            } else if progress.is_cancelled() && self.cut && source.exists() {
+                result.failed.push(source);
+            } else if self.cut && !overwrite {
+                //Moves that replaced something can't be undone, as the old file is gone
+                result.done.push((source, destination));
+            }
+        }
+
+        result
+    }
+}
+
+//True if `source` was modified after `destination`
+pub fn is_newer(source: &Path, destination: &Path) -> bool {
+    let modified = |path: &Path| fs::symlink_metadata(path).and_then(|m| m.modified());
+    match (modified(source), modified(destination)) {
+        (Ok(source), Ok(destination)) => source > destination,
+        _ => false,
+    }
+}
+
+//Finds a free name next to `path` in the form "name (1).ext"
+pub fn unique_destination(path: &Path) -> PathBuf {
+    //Split on the raw bytes so names that aren't valid UTF-8 keep their exact bytes
+    let name = path.file_name().unwrap_or_default().as_bytes();
+
+    //Dotfiles and directories keep their whole name as the stem
+    let is_dir = fs::symlink_metadata(path)
+        .map(|m| m.is_dir())
+        .unwrap_or(false);
+    let (stem, extension) = match name.iter().rposition(|byte| *byte == b'.') {
+        Some(index) if index > 0 && !is_dir => (&name[..index], &name[index..]),
+        _ => (name, &[][..]),
+    };
+
+    let mut number = 1;
+    loop {
+        let mut candidate_name = stem.to_vec();
+        candidate_name.extend_from_slice(format!(" ({})", number).as_bytes());
+        candidate_name.extend_from_slice(extension);
+        let candidate = path.with_file_name(OsStr::from_bytes(&candidate_name));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        number += 1;
+    }
+}
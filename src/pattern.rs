@@ -0,0 +1,77 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+use globset::{GlobBuilder, GlobMatcher};
+use regex::bytes::{Regex, RegexBuilder};
+
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+//A pattern matched against file names. Matching is smart-case: it ignores case unless the
+//pattern contains an uppercase letter.
+pub struct Pattern {
+    pub text: String,
+    matcher: Matcher,
+}
+
+impl Pattern {
+    //"/regex/" is a regular expression, anything else is a glob.
+    //A glob without wildcards matches names containing it, so "main" finds "main.rs".
+    pub fn parse(text: &str) -> Result<Pattern, String> {
+        if text.len() > 1 && text.starts_with('/') && text.ends_with('/') {
+            //Keep the slashes in the text, so it reads back the way it was typed
+            let mut pattern = Pattern::regex(&text[1..text.len() - 1])?;
+            pattern.text = text.to_string();
+            Ok(pattern)
+        } else {
+            Pattern::glob(text)
+        }
+    }
+
+    pub fn glob(text: &str) -> Result<Pattern, String> {
+        let has_wildcards = text.contains(['*', '?', '[', '{']);
+        let glob = if has_wildcards {
+            text.to_string()
+        } else {
+            format!("*{}*", globset::escape(text))
+        };
+
+        let matcher = GlobBuilder::new(&glob)
+            .case_insensitive(!has_uppercase(text))
+            .literal_separator(true)
+            .build()
+            .map_err(|err| err.kind().to_string())?
+            .compile_matcher();
+
+        Ok(Pattern {
+            text: text.to_string(),
+            matcher: Matcher::Glob(matcher),
+        })
+    }
+
+    pub fn regex(text: &str) -> Result<Pattern, String> {
+        let regex = RegexBuilder::new(text)
+            .case_insensitive(!has_uppercase(text))
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        Ok(Pattern {
+            text: text.to_string(),
+            matcher: Matcher::Regex(regex),
+        })
+    }
+
+    //Works on the raw bytes, so names that aren't valid UTF-8 can still match
+    pub fn is_match(&self, name: &OsStr) -> bool {
+        match &self.matcher {
+            Matcher::Glob(glob) => glob.is_match(name),
+            Matcher::Regex(regex) => regex.is_match(name.as_bytes()),
+        }
+    }
+}
+
+pub fn has_uppercase(text: &str) -> bool {
+    text.chars().any(char::is_uppercase)
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::events::Waker;
+use crate::file_ops::{self, DirectoryItem, FileKind};
+use crate::highlight::{Highlighter, Span};
+use crate::sort::SortOrder;
+
+//How much of a file is read for its preview
+const MAX_BYTES: u64 = 64 * 1024;
+//Lines of text, or entries of a directory, that are kept
+const MAX_LINES: usize = 200;
+//How much of a binary file is shown as hex
+const HEX_BYTES: usize = 512;
+//Previews kept for entries that were selected recently
+const CACHE_SIZE: usize = 64;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PreviewKind {
+    Text,
+    Binary,
+    Directory,
+    Info,
+    Error,
+}
+
+pub struct Preview {
+    pub kind: PreviewKind,
+    pub lines: Vec<String>,
+    //The same lines syntax highlighted, for text in a language that's known
+    pub highlighted: Option<Vec<Vec<Span>>>,
+    stamp: Stamp,
+}
+
+//What a preview was made from. When any of it changes the preview is made again.
+#[derive(Clone, Copy, PartialEq)]
+struct Stamp {
+    modified: i64,
+    changed: i64,
+    size: u64,
+    inode: u64,
+}
+
+impl Stamp {
+    //Symlinks are followed, so editing a link's target refreshes the link's preview
+    fn of(item: &DirectoryItem) -> Stamp {
+        match fs::metadata(item.path()) {
+            Ok(metadata) => Stamp {
+                modified: metadata.mtime(),
+                changed: metadata.ctime(),
+                size: metadata.len(),
+                inode: metadata.ino(),
+            },
+            Err(_) => Stamp {
+                modified: item.modified,
+                changed: item.changed,
+                size: item.size,
+                inode: item.inode,
+            },
+        }
+    }
+}
+
+struct Request {
+    item: DirectoryItem,
+    stamp: Stamp,
+    show_hidden: bool,
+    sort: SortOrder,
+}
+
+//Makes previews on a worker thread and keeps the recent ones, so moving through the listing
+//never waits on the disk
+pub struct Previewer {
+    cache: HashMap<PathBuf, Preview>,
+    //Cached paths, oldest first
+    order: VecDeque<PathBuf>,
+    pending: Option<PathBuf>,
+    requests: Sender<Request>,
+    results: Receiver<(PathBuf, Preview)>,
+}
+
+impl Previewer {
+    //Text is highlighted with the named theme, or not at all without one. `waker` is woken when a
+    //preview is ready.
+    pub fn new(theme: Option<String>, waker: Waker) -> Previewer {
+        let (requests, request_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        thread::spawn(move || work(request_receiver, result_sender, theme, waker));
+
+        Previewer {
+            cache: HashMap::new(),
+            order: VecDeque::new(),
+            pending: None,
+            requests,
+            results,
+        }
+    }
+
+    //Takes finished previews, and asks for one of `item` if there's no up to date one yet.
+    //True if any previews came in.
+    pub fn update(
+        &mut self,
+        item: Option<&DirectoryItem>,
+        show_hidden: bool,
+        sort: SortOrder,
+    ) -> bool {
+        let mut received = false;
+        while let Ok((path, preview)) = self.results.try_recv() {
+            if self.pending.as_ref() == Some(&path) {
+                self.pending = None;
+            }
+            self.insert(path, preview);
+            received = true;
+        }
+
+        let item = match item {
+            Some(item) => item,
+            None => return received,
+        };
+        let stamp = Stamp::of(item);
+        let up_to_date = self
+            .cache
+            .get(item.path())
+            .is_some_and(|preview| preview.stamp == stamp);
+        if up_to_date || self.pending.as_deref() == Some(item.path()) {
+            return received;
+        }
+
+        self.pending = Some(item.path().to_path_buf());
+        let _ = self.requests.send(Request {
+            item: item.clone(),
+            stamp,
+            show_hidden,
+            sort,
+        });
+        received
+    }
+
+    //The preview of `path`, which may be out of date until a newer one is made
+    pub fn get(&self, path: &Path) -> Option<&Preview> {
+        self.cache.get(path)
+    }
+
+    fn insert(&mut self, path: PathBuf, preview: Preview) {
+        if self.cache.insert(path.clone(), preview).is_some() {
+            self.order.retain(|cached| *cached != path);
+        }
+        self.order.push_back(path);
+
+        while self.order.len() > CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+    }
+}
+
+//Only the latest request matters, as anything before it has already been scrolled past
+fn work(
+    requests: Receiver<Request>,
+    results: Sender<(PathBuf, Preview)>,
+    theme: Option<String>,
+    waker: Waker,
+) {
+    //Made the first time some text needs it
+    let mut highlighter = None;
+
+    while let Ok(mut request) = requests.recv() {
+        while let Ok(newer) = requests.try_recv() {
+            request = newer;
+        }
+
+        let (kind, lines) = match load(&request) {
+            Ok(loaded) => loaded,
+            Err(err) => (PreviewKind::Error, vec![err.to_string()]),
+        };
+        let highlighted = match &theme {
+            Some(theme) if kind == PreviewKind::Text => highlighter
+                .get_or_insert_with(|| Highlighter::new(theme))
+                .highlight(request.item.path(), &lines),
+            _ => None,
+        };
+        let preview = Preview {
+            kind,
+            lines,
+            highlighted,
+            stamp: request.stamp,
+        };
+        if results
+            .send((request.item.path().to_path_buf(), preview))
+            .is_err()
+        {
+            return;
+        }
+        waker.wake();
+    }
+}
+
+fn load(request: &Request) -> io::Result<(PreviewKind, Vec<String>)> {
+    let item = &request.item;
+    match item.kind {
+        FileKind::Directory
+        | FileKind::Symlink {
+            to_directory: true, ..
+        } => load_directory(item.path(), request.show_hidden, request.sort),
+        FileKind::File
+        | FileKind::Symlink {
+            broken: false,
+            to_directory: false,
+            ..
+        } if fs::metadata(item.path())?.is_file() => load_file(item.path()),
+        //Pipes, sockets and devices can block or never end, so they are never read
+        _ => Ok((PreviewKind::Info, item.describe())),
+    }
+}
+
+//The start of a text file, or a hex dump if it looks binary
+fn load_file(path: &Path) -> io::Result<(PreviewKind, Vec<String>)> {
+    let mut bytes = Vec::new();
+    File::open(path)?.take(MAX_BYTES).read_to_end(&mut bytes)?;
+
+    if bytes.is_empty() {
+        return Ok((PreviewKind::Text, vec![String::from("(empty file)")]));
+    }
+    if !is_text(&bytes) {
+        return Ok((
+            PreviewKind::Binary,
+            hex_dump(&bytes[..bytes.len().min(HEX_BYTES)]),
+        ));
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let lines = text.lines().take(MAX_LINES).map(display_line).collect();
+    Ok((PreviewKind::Text, lines))
+}
+
+//UTF-8 without NUL bytes. A character cut off by the end of what was read still counts.
+pub fn is_text(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+//Tabs become spaces and other control characters are dropped, so the line draws as it looks
+fn display_line(line: &str) -> String {
+    let mut display = String::with_capacity(line.len());
+    for character in line.chars() {
+        if character == '\t' {
+            display.push_str("    ");
+        } else if !character.is_control() {
+            display.push(character);
+        }
+    }
+    display
+}
+
+//16 bytes a line: offset, bytes in hex, then the printable ones
+fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|byte| {
+                    if byte.is_ascii_graphic() || *byte == b' ' {
+                        *byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {:<47}  |{}|", line * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
+//The entries of a directory, sorted and filtered like the listing
+fn load_directory(
+    path: &Path,
+    show_hidden: bool,
+    sort: SortOrder,
+) -> io::Result<(PreviewKind, Vec<String>)> {
+    let mut items: Vec<DirectoryItem> = fs::read_dir(path)?
+        .flatten()
+        .filter_map(|entry| DirectoryItem::from_path(&entry.path()).ok())
+        .filter(|item| show_hidden || !item.is_hidden())
+        .collect();
+    sort.sort(&mut items);
+
+    if items.is_empty() {
+        return Ok((
+            PreviewKind::Directory,
+            vec![String::from("(empty directory)")],
+        ));
+    }
+
+    let mut lines: Vec<String> = items
+        .iter()
+        .take(MAX_LINES)
+        .map(|item| {
+            let name = file_ops::display_name(item.name());
+            if item.is_directory() {
+                format!("{}/", name)
+            } else {
+                name
+            }
+        })
+        .collect();
+    if items.len() > MAX_LINES {
+        lines.push(format!("… and {} more", items.len() - MAX_LINES));
+    }
+
+    Ok((PreviewKind::Directory, lines))
+}
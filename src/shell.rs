@@ -0,0 +1,300 @@
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::jobs::{JobResult, Progress};
+
+//Output lines kept in the log across all commands; the oldest are dropped first
+const MAX_LOG_LINES: usize = 5000;
+
+//What the placeholders in a command stand for
+pub struct Placeholders {
+    pub selected: Option<PathBuf>,
+    //The marked entries, or the selected one if nothing is marked
+    pub targets: Vec<PathBuf>,
+    pub directory: PathBuf,
+}
+
+//Replaces %f with the selected entry, %F with the marked entries, %d with the current directory
+//and %% with a single '%'. Paths are quoted for the shell.
+pub fn expand(command: &str, placeholders: &Placeholders) -> Result<OsString, String> {
+    let mut expanded = OsString::new();
+    let mut rest = command;
+
+    while let Some(index) = rest.find('%') {
+        expanded.push(&rest[..index]);
+        let mut characters = rest[index + 1..].chars();
+        match characters.next() {
+            Some('f') => match &placeholders.selected {
+                Some(path) => expanded.push(quote(path.as_os_str())),
+                None => return Err(String::from("Nothing selected for %f")),
+            },
+            Some('F') => {
+                if placeholders.targets.is_empty() {
+                    return Err(String::from("Nothing selected for %F"));
+                }
+                for (number, path) in placeholders.targets.iter().enumerate() {
+                    if number > 0 {
+                        expanded.push(" ");
+                    }
+                    expanded.push(quote(path.as_os_str()));
+                }
+            }
+            Some('d') => expanded.push(quote(placeholders.directory.as_os_str())),
+            Some('%') => expanded.push("%"),
+            //Anything else is left alone, e.g. date +%Y
+            Some(other) => {
+                expanded.push("%");
+                expanded.push(other.to_string());
+            }
+            None => expanded.push("%"),
+        }
+        rest = characters.as_str();
+    }
+    expanded.push(rest);
+
+    Ok(expanded)
+}
+
+//Wraps `text` in single quotes, so the shell takes it as one word whatever it contains
+pub fn quote(text: &OsStr) -> OsString {
+    let mut quoted = Vec::with_capacity(text.len() + 2);
+    quoted.push(b'\'');
+    for byte in text.as_bytes() {
+        if *byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(*byte);
+        }
+    }
+    quoted.push(b'\'');
+    OsStr::from_bytes(&quoted).to_os_string()
+}
+
+//Runs the script given as $1 in a shell of its own, so even an `exit` in it gets to the prompt
+const PROMPT_SCRIPT: &str = "sh -c \"$1\"
+status=$?
+printf '\\n[exit %d] Press Enter to return to tfex' \"$status\"
+read _
+exit \"$status\"";
+
+//`script` run by the shell in `directory`, followed by a prompt so its output can be read before
+//the listing comes back. The script's exit status is kept.
+pub fn foreground_command(script: &OsStr, directory: &Path) -> Command {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(PROMPT_SCRIPT)
+        .arg("sh")
+        .arg(script)
+        .current_dir(directory);
+    command
+}
+
+//A background command and what it has printed so far
+pub struct LogEntry {
+    pub job_id: usize,
+    pub command: String,
+    pub lines: Vec<String>,
+    //How it ended, or None while it's running
+    pub status: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Log {
+    pub entries: Vec<LogEntry>,
+}
+
+impl Log {
+    fn push_line(&mut self, job_id: usize, line: String) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.job_id == job_id) {
+            entry.lines.push(line);
+        }
+
+        //Drop from the oldest commands, removing finished ones once they're empty
+        let mut total: usize = self.entries.iter().map(|entry| entry.lines.len()).sum();
+        let mut index = 0;
+        while total > MAX_LOG_LINES && index < self.entries.len() {
+            let entry = &mut self.entries[index];
+            let dropped = (total - MAX_LOG_LINES).min(entry.lines.len());
+            entry.lines.drain(..dropped);
+            total -= dropped;
+            if entry.lines.is_empty() && entry.status.is_some() {
+                self.entries.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    fn finish(&mut self, job_id: usize, status: String) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.job_id == job_id) {
+            entry.status = Some(status);
+        }
+    }
+
+    //Every command with its output, oldest first, for viewing
+    pub fn describe(&self) -> Vec<String> {
+        if self.entries.is_empty() {
+            return vec![String::from("No background commands have been run")];
+        }
+
+        let mut lines = Vec::new();
+        for entry in &self.entries {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("#{} $ {}", entry.job_id, entry.command));
+            lines.extend(entry.lines.iter().map(|line| format!("  {}", line)));
+            lines.push(match &entry.status {
+                Some(status) => format!("[{}]", status),
+                None => String::from("[running]"),
+            });
+        }
+        lines
+    }
+}
+
+//Runs `script` with the shell in `directory` as a job, adding its output and stderr to the log
+//entry for `job_id` as they are printed. Pausing and cancelling the job stop and kill the shell.
+pub fn run_background(
+    script: OsString,
+    directory: PathBuf,
+    log: Arc<Mutex<Log>>,
+    job_id: usize,
+    progress: Arc<Progress>,
+) -> JobResult {
+    let status = match run(&script, &directory, &log, job_id, &progress) {
+        Ok(status) => status,
+        Err(err) => {
+            let message = format!("couldn't run the shell: {}", err);
+            finish(&log, job_id, message.clone());
+            return JobResult::error(Error::Other(message));
+        }
+    };
+
+    let mut result = JobResult::default();
+    let description = if progress.is_cancelled() {
+        String::from("cancelled")
+    } else if status.success() {
+        String::from("exit 0")
+    } else {
+        let description = match status.code() {
+            Some(code) => format!("exit {}", code),
+            None => status.to_string(),
+        };
+        result.errors.push(Error::Other(description.clone()));
+        description
+    };
+    finish(&log, job_id, description);
+    result
+}
+
+fn run(
+    script: &OsStr,
+    directory: &Path,
+    log: &Arc<Mutex<Log>>,
+    job_id: usize,
+    progress: &Progress,
+) -> io::Result<std::process::ExitStatus> {
+    //stdout and stderr share a pipe, so the log keeps them in the order they were printed
+    let (reader, writer) = io::pipe()?;
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .stdout(writer.try_clone()?)
+        .stderr(writer)
+        //Its own process group, so signals reach whatever the shell starts too
+        .process_group(0)
+        .spawn()?;
+
+    let reader_log = Arc::clone(log);
+    thread::spawn(move || {
+        for line in BufReader::new(reader).split(b'\n').map_while(Result::ok) {
+            let line = display_line(&String::from_utf8_lossy(&line));
+            if let Ok(mut log) = reader_log.lock() {
+                log.push_line(job_id, line);
+            }
+        }
+    });
+
+    let group = -(child.id() as libc::pid_t);
+    let mut stopped = false;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if progress.is_cancelled() {
+            unsafe { libc::kill(group, libc::SIGKILL) };
+            return child.wait();
+        }
+        if progress.is_paused() != stopped {
+            stopped = progress.is_paused();
+            let signal = if stopped {
+                libc::SIGSTOP
+            } else {
+                libc::SIGCONT
+            };
+            unsafe { libc::kill(group, signal) };
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+//Output as it can be drawn in the log: tabs become spaces, and escape sequences such as colours
+//and other control characters are dropped, so they can't act on the terminal
+fn display_line(line: &str) -> String {
+    let mut display = String::with_capacity(line.len());
+    let mut characters = line.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '\t' => display.push_str("    "),
+            '\x1b' => match characters.next() {
+                //CSI, e.g. colours: parameters up to a final byte from '@' to '~'
+                Some('[') => {
+                    for character in characters.by_ref() {
+                        if ('@'..='~').contains(&character) {
+                            break;
+                        }
+                    }
+                }
+                //OSC, e.g. setting the title: up to BEL or ESC \
+                Some(']') => {
+                    while let Some(character) = characters.next() {
+                        if character == '\x07' {
+                            break;
+                        }
+                        if character == '\x1b' && characters.peek() == Some(&'\\') {
+                            characters.next();
+                            break;
+                        }
+                    }
+                }
+                //Anything else is ESC and one character
+                _ => {}
+            },
+            _ if character.is_control() => {}
+            _ => display.push(character),
+        }
+    }
+
+    display
+}
+
+fn finish(log: &Mutex<Log>, job_id: usize, status: String) {
+    if let Ok(mut log) = log.lock() {
+        log.finish(job_id, status);
+    }
+}
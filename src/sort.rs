@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+use crate::file_ops::{DirectoryItem, FileKind};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Extension,
+    Kind,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 5] = [
+        SortKey::Name,
+        SortKey::Size,
+        SortKey::Modified,
+        SortKey::Extension,
+        SortKey::Kind,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "mtime",
+            SortKey::Extension => "ext",
+            SortKey::Kind => "kind",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SortKey> {
+        match name {
+            "name" => Some(SortKey::Name),
+            "size" => Some(SortKey::Size),
+            "mtime" | "modified" => Some(SortKey::Modified),
+            "ext" | "extension" => Some(SortKey::Extension),
+            "kind" | "type" => Some(SortKey::Kind),
+            _ => None,
+        }
+    }
+
+    //The key after this one, for cycling through them with a single key
+    pub fn next(self) -> SortKey {
+        let index = SortKey::ALL
+            .iter()
+            .position(|key| *key == self)
+            .unwrap_or(0);
+        SortKey::ALL[(index + 1) % SortKey::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub descending: bool,
+    pub directories_first: bool,
+}
+
+impl Default for SortOrder {
+    fn default() -> SortOrder {
+        SortOrder {
+            key: SortKey::Name,
+            descending: false,
+            directories_first: true,
+        }
+    }
+}
+
+impl SortOrder {
+    //Short description for the file list title, e.g. "size↓, dirs first"
+    pub fn describe(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.key.name(),
+            if self.descending { "↓" } else { "↑" },
+            if self.directories_first {
+                ", dirs first"
+            } else {
+                ""
+            }
+        )
+    }
+
+    pub fn sort(&self, items: &mut [DirectoryItem]) {
+        items.sort_by(|a, b| self.compare(a, b));
+    }
+
+    fn compare(&self, a: &DirectoryItem, b: &DirectoryItem) -> Ordering {
+        //Grouping directories first isn't affected by the direction
+        if self.directories_first && a.is_directory() != b.is_directory() {
+            return b.is_directory().cmp(&a.is_directory());
+        }
+
+        let ordering = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Extension => natural_cmp(extension(a.name()), extension(b.name())),
+            SortKey::Kind => kind_rank(a).cmp(&kind_rank(b)),
+        }
+        //Anything that compares equal falls back to the name, so the order is always stable
+        .then_with(|| natural_cmp(a.name(), b.name()));
+
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+//Compares names the way people expect: case-insensitively, and with runs of digits compared
+//as numbers, so "file2" comes before "file10" and "v1.9" before "v1.10"
+pub fn natural_cmp(a: &OsStr, b: &OsStr) -> Ordering {
+    let (a_bytes, b_bytes) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+
+    while i < a_bytes.len() && j < b_bytes.len() {
+        if a_bytes[i].is_ascii_digit() && b_bytes[j].is_ascii_digit() {
+            let a_end = digits_end(a_bytes, i);
+            let b_end = digits_end(b_bytes, j);
+            let ordering = compare_numbers(&a_bytes[i..a_end], &b_bytes[j..b_end]);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            i = a_end;
+            j = b_end;
+        } else {
+            let ordering = a_bytes[i]
+                .to_ascii_lowercase()
+                .cmp(&b_bytes[j].to_ascii_lowercase());
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+
+    //The shorter name comes first, then fall back to the exact bytes so "A" and "a" don't tie
+    (a_bytes.len() - i)
+        .cmp(&(b_bytes.len() - j))
+        .then_with(|| a_bytes.cmp(b_bytes))
+}
+
+fn digits_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|byte| !byte.is_ascii_digit())
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+//Compares two runs of digits by value without parsing, so any length works
+fn compare_numbers(a: &[u8], b: &[u8]) -> Ordering {
+    let trim =
+        |digits: &[u8]| -> usize { digits.iter().take_while(|digit| **digit == b'0').count() };
+    let (a_value, b_value) = (&a[trim(a)..], &b[trim(b)..]);
+
+    a_value
+        .len()
+        .cmp(&b_value.len())
+        .then_with(|| a_value.cmp(b_value))
+        //Equal values with more leading zeros sort after, e.g. "1" before "01"
+        .then_with(|| a.len().cmp(&b.len()))
+}
+
+//The part of a name after its last dot, or nothing for dotfiles and names without one
+fn extension(name: &OsStr) -> &OsStr {
+    let bytes = name.as_bytes();
+    match bytes.iter().rposition(|byte| *byte == b'.') {
+        Some(index) if index > 0 => OsStr::from_bytes(&bytes[index + 1..]),
+        _ => OsStr::new(""),
+    }
+}
+
+fn kind_rank(item: &DirectoryItem) -> u8 {
+    match item.kind {
+        FileKind::Directory => 0,
+        FileKind::Symlink { .. } => 1,
+        FileKind::File => 2,
+        FileKind::Fifo => 3,
+        FileKind::Socket => 4,
+        FileKind::BlockDevice | FileKind::CharDevice => 5,
+    }
+}
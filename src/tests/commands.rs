@@ -0,0 +1,167 @@
+use std::fs;
+
+use super::Harness;
+use crate::app::Mode;
+
+#[test]
+fn directory_command_creates_and_lists_a_directory() {
+    let mut harness = Harness::new(&["file"]);
+
+    harness.type_keys(":dir new one\n");
+    assert!(harness.path("new one").is_dir());
+    harness.wait_for_changes(|harness| harness.listing() == ["new one", "file"]);
+    assert!(harness.message().is_none());
+}
+
+#[test]
+fn rename_can_be_undone_and_redone() {
+    let mut harness = Harness::new(&["old.txt"]);
+
+    harness.type_keys(":rename new.txt\n");
+    assert!(harness.path("new.txt").exists());
+    assert!(!harness.path("old.txt").exists());
+    harness.wait_for_changes(|harness| harness.listing() == ["new.txt"]);
+
+    harness.type_keys("u");
+    assert!(harness.path("old.txt").exists());
+    assert!(!harness.path("new.txt").exists());
+
+    harness.press(termion::event::Key::Ctrl('r'));
+    assert!(harness.path("new.txt").exists());
+}
+
+#[test]
+fn rename_refuses_to_replace_an_entry_or_move_out_of_the_directory() {
+    let mut harness = Harness::new(&["a.txt", "b.txt"]);
+    harness.select("a.txt");
+
+    //Renaming to the same name changes nothing and isn't refused
+    harness.type_keys(":rename a.txt\n");
+    assert!(harness.path("a.txt").exists());
+    assert!(harness.message().is_none());
+
+    harness.type_keys(":rename b.txt\n");
+    assert_eq!(fs::read_to_string(harness.path("b.txt")).unwrap(), "b.txt");
+    assert!(harness.path("a.txt").exists());
+    let expected = format!(
+        "Couldn't rename {}: {} already exists",
+        harness.path("a.txt").display(),
+        harness.path("b.txt").display()
+    );
+    assert_eq!(harness.message(), Some(expected.as_str()));
+
+    harness.type_keys(":rename ../a.txt\n");
+    assert!(harness.path("a.txt").exists());
+    assert_eq!(
+        harness.message(),
+        Some("Not a name: ../a.txt (names can't contain '/')")
+    );
+    assert!(harness.app.journal.undo.is_empty());
+}
+
+#[test]
+fn unknown_commands_show_an_error() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":frobnicate\n");
+    assert_eq!(harness.message(), Some("Not a command: frobnicate"));
+    assert_eq!(harness.app.mode, Mode::Browse);
+}
+
+#[test]
+fn escape_leaves_command_mode_without_running_anything() {
+    let mut harness = Harness::new(&["file"]);
+
+    harness.type_keys(":dir nope\x1b");
+    assert_eq!(harness.app.mode, Mode::Browse);
+    assert!(harness.app.command_line.is_empty());
+    assert!(!harness.path("nope").exists());
+}
+
+#[test]
+fn sort_orders_by_size_either_way() {
+    let mut harness = Harness::new(&[]);
+    fs::write(harness.path("small"), "x").unwrap();
+    fs::write(harness.path("large"), "x".repeat(100)).unwrap();
+    fs::write(harness.path("medium"), "x".repeat(10)).unwrap();
+    harness.app.refresh().unwrap();
+
+    harness.type_keys(":sort size desc\n");
+    assert_eq!(harness.listing(), ["large", "medium", "small"]);
+
+    harness.type_keys(":sort reverse\n");
+    assert_eq!(harness.listing(), ["small", "medium", "large"]);
+}
+
+#[test]
+fn shell_commands_fill_in_placeholders() {
+    let mut harness = Harness::new(&["it's here", "other"]);
+    harness.type_keys(" j ");
+
+    harness.type_keys(":!echo %F in %d\n");
+    let command = harness
+        .app
+        .foreground_command
+        .take()
+        .expect("a command to run");
+    let arguments: Vec<String> = command
+        .get_args()
+        .map(|argument| argument.to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(command.get_program(), "sh");
+    assert_eq!(
+        arguments.last().unwrap(),
+        &format!(
+            "echo '{}' '{}' in '{}'",
+            harness
+                .path("it's here")
+                .display()
+                .to_string()
+                .replace('\'', "'\\''"),
+            harness.path("other").display(),
+            harness.dir.path().display()
+        )
+    );
+}
+
+#[test]
+fn background_shell_commands_log_their_output() {
+    let mut harness = Harness::new(&["file"]);
+
+    harness.type_keys(":!echo out; echo err >&2; exit 3 &\n");
+    harness.wait_for_jobs();
+
+    let log = harness.app.shell_log.lock().unwrap().describe();
+    assert_eq!(log[1..], ["  out", "  err", "[exit 3]"]);
+    assert!(harness.message().unwrap().contains("exit 3"));
+}
+
+#[test]
+fn background_output_is_logged_without_escape_sequences() {
+    let mut harness = Harness::new(&["file"]);
+
+    harness.type_keys(
+        ":!printf '\\033[2J\\033[01;34mblue\\033[0m\\tx\\033]0;title\\007\\a\\r\\n' &\n",
+    );
+    harness.wait_for_jobs();
+
+    let log = harness.app.shell_log.lock().unwrap().describe();
+    assert_eq!(log[1], "  blue    x");
+}
+
+#[test]
+fn opening_a_pipe_is_refused_with_its_name_escaped() {
+    let mut harness = Harness::new(&[]);
+    let path = harness.path("pipe\x1b[2J");
+    let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+    harness.app.refresh().unwrap();
+
+    harness.type_keys("\n");
+    assert!(harness.app.foreground_command.is_none());
+    let expected = format!(
+        "{}/pipe\\x1B[2J isn't a file that can be opened",
+        harness.dir.path().display()
+    );
+    assert_eq!(harness.message(), Some(expected.as_str()));
+}
@@ -0,0 +1,210 @@
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{test_config, Harness, TIMEOUT};
+use crate::app::Mode;
+use crate::config::{Config, Operation};
+use crate::copy::verify_copy;
+
+#[test]
+fn copy_and_paste_into_another_directory() {
+    let mut harness = Harness::new(&["target/", "a.txt", "b.txt"]);
+    harness.select("a.txt");
+
+    harness.type_keys("c");
+    harness.select("target");
+    harness.type_keys("\nv");
+    harness.wait_for_jobs();
+
+    assert_eq!(harness.listing(), ["a.txt"]);
+    assert_eq!(
+        fs::read_to_string(harness.path("target/a.txt")).unwrap(),
+        "a.txt"
+    );
+    assert!(harness.path("a.txt").exists());
+}
+
+#[test]
+fn cut_and_paste_moves_every_marked_file() {
+    let mut harness = Harness::new(&["target/", "a.txt", "b.txt", "c.txt"]);
+    harness.select("a.txt");
+
+    harness.type_keys(" j x");
+    harness.select("target");
+    harness.type_keys("\nv");
+    harness.wait_for_jobs();
+
+    assert_eq!(harness.listing(), ["a.txt", "c.txt"]);
+    assert!(!harness.path("a.txt").exists());
+    assert!(!harness.path("c.txt").exists());
+    assert!(harness.path("b.txt").exists());
+    assert!(harness.app.clipboard.is_none());
+}
+
+#[test]
+fn pasting_over_an_existing_file_asks_what_to_do() {
+    let mut harness = Harness::new(&["target/a.txt", "a.txt"]);
+    fs::write(harness.path("a.txt"), "new").unwrap();
+    harness.select("a.txt");
+
+    harness.type_keys("c");
+    harness.select("target");
+    harness.type_keys("\nv");
+    assert_eq!(harness.app.mode, Mode::Conflict);
+
+    harness.type_keys("r");
+    harness.wait_for_jobs();
+    assert_eq!(harness.listing(), ["a (1).txt", "a.txt"]);
+    assert_eq!(
+        fs::read_to_string(harness.path("target/a (1).txt")).unwrap(),
+        "new"
+    );
+    assert_eq!(
+        fs::read_to_string(harness.path("target/a.txt")).unwrap(),
+        "target/a.txt"
+    );
+}
+
+#[test]
+fn skipping_a_conflict_leaves_the_existing_file() {
+    let mut harness = Harness::new(&["target/a.txt", "a.txt"]);
+    harness.select("a.txt");
+
+    harness.type_keys("c");
+    harness.select("target");
+    harness.type_keys("\nvs");
+    harness.wait_for_jobs();
+
+    assert_eq!(harness.listing(), ["a.txt"]);
+    assert_eq!(
+        fs::read_to_string(harness.path("target/a.txt")).unwrap(),
+        "target/a.txt"
+    );
+}
+
+#[test]
+fn purge_asks_first_when_configured_to() {
+    let config = Config {
+        confirm: [Operation::Purge].iter().cloned().collect(),
+        ..test_config()
+    };
+    let mut harness = Harness::with_config(&["dir/inside", "keep"], config);
+
+    harness.type_keys(":purge\n");
+    assert_eq!(harness.app.mode, Mode::Confirm);
+    harness.type_keys("n");
+    assert_eq!(harness.app.mode, Mode::Browse);
+    assert!(harness.path("dir/inside").exists());
+
+    harness.type_keys(":purge\ny");
+    harness.wait_for_jobs();
+    assert!(!harness.path("dir").exists());
+    assert_eq!(harness.listing(), ["keep"]);
+}
+
+#[test]
+fn merging_a_directory_replaces_symlinks_inside_it_instead_of_writing_through_them() {
+    let mut harness = Harness::new(&["target/src/", "src/a", "outside"]);
+    symlink(harness.path("outside"), harness.path("target/src/a")).unwrap();
+    symlink("old", harness.path("target/src/link")).unwrap();
+    symlink("new", harness.path("src/link")).unwrap();
+    harness.select("src");
+
+    harness.type_keys("c");
+    harness.select("target");
+    harness.type_keys("\nvo");
+    harness.wait_for_jobs();
+
+    assert!(harness.message().is_none());
+    assert_eq!(
+        fs::read_to_string(harness.path("outside")).unwrap(),
+        "outside"
+    );
+    let merged = harness.path("target/src/a");
+    assert!(fs::symlink_metadata(&merged).unwrap().is_file());
+    assert_eq!(fs::read_to_string(merged).unwrap(), "src/a");
+    assert_eq!(
+        fs::read_link(harness.path("target/src/link")).unwrap(),
+        Path::new("new")
+    );
+}
+
+#[test]
+fn confirmations_ask_straight_away_and_measure_in_the_background() {
+    let config = Config {
+        confirm: [Operation::Purge].iter().cloned().collect(),
+        ..test_config()
+    };
+    let mut harness = Harness::with_config(&["dir/a", "dir/b"], config);
+
+    harness.type_keys(":purge\n");
+    let message = || harness.app.confirmation.as_ref().unwrap().message();
+    assert!(message().starts_with("Permanently delete 'dir'?\n"));
+
+    let started = Instant::now();
+    while !message().contains("1 directory, 2 files") {
+        assert!(started.elapsed() < TIMEOUT, "never measured: {}", message());
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(message().ends_with("This can't be undone."));
+}
+
+//Makes a chain of directories whose full path is too long to use, so removing it fails even as root
+fn make_too_deep(parent: &Path) {
+    let name = CString::new("d".repeat(200)).unwrap();
+    let parent = CString::new(parent.as_os_str().as_bytes()).unwrap();
+    unsafe {
+        let mut directory = libc::open(parent.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY);
+        for _ in 0..25 {
+            assert_eq!(libc::mkdirat(directory, name.as_ptr(), 0o755), 0);
+            let child = libc::openat(directory, name.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY);
+            libc::close(directory);
+            directory = child;
+        }
+        libc::close(directory);
+    }
+}
+
+#[test]
+fn purge_removes_everything_it_can_and_reports_the_rest() {
+    let mut harness = Harness::new(&["tree/a", "tree/b/c", "tree/x/", "tree/y/", "tree/z"]);
+    make_too_deep(&harness.path("tree/x"));
+    make_too_deep(&harness.path("tree/y"));
+    harness.select("tree");
+
+    harness.type_keys(":purge\n");
+    harness.wait_for_jobs();
+
+    assert!(!harness.path("tree/a").exists());
+    assert!(!harness.path("tree/b").exists());
+    assert!(!harness.path("tree/z").exists());
+    assert!(harness.path("tree/x").exists());
+    assert!(harness.path("tree/y").exists());
+    let message = harness.message().unwrap();
+    assert_eq!(
+        message.matches("File name too long").count(),
+        2,
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn a_copy_only_verifies_with_the_same_contents() {
+    let harness = Harness::new(&["source/a", "source/b", "copy/a", "copy/b"]);
+    assert!(verify_copy(&harness.path("source"), &harness.path("copy")).is_err());
+
+    fs::write(harness.path("copy/a"), "source/a").unwrap();
+    fs::write(harness.path("copy/b"), "source/b").unwrap();
+    assert!(verify_copy(&harness.path("source"), &harness.path("copy")).is_ok());
+
+    //Same size, different bytes
+    fs::write(harness.path("copy/b"), "source/c").unwrap();
+    let err = verify_copy(&harness.path("source"), &harness.path("copy")).unwrap_err();
+    assert!(err.to_string().ends_with("differs: contents"), "{}", err);
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Harness, TIMEOUT};
+use crate::app::Mode;
+
+//Takes in results until the search has stopped
+fn wait_for_grep(harness: &mut Harness) {
+    let started = Instant::now();
+    while harness.app.update_grep() {
+        assert!(started.elapsed() < TIMEOUT, "the search never finished");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn grep_lists_matching_lines_of_text_files() {
+    let mut harness = Harness::new(&["src/"]);
+    fs::write(
+        harness.path("src/main.rs"),
+        "fn main() {}\n// Needle here\n",
+    )
+    .unwrap();
+    fs::write(harness.path("notes.md"), "no match\n").unwrap();
+    fs::write(harness.path("data.bin"), b"needle\n\0\x01\x02").unwrap();
+    let mut image = b"\x89PNG\0\0".to_vec();
+    image.extend("needle ".repeat(100_000).as_bytes());
+    fs::write(harness.path("image.png"), image).unwrap();
+
+    harness.type_keys(":grep needle\n");
+    assert_eq!(harness.app.mode, Mode::Grep);
+    wait_for_grep(&mut harness);
+
+    let grep = harness.app.grep.as_ref().unwrap();
+    let found: Vec<(String, usize, &str)> = grep
+        .matches
+        .iter()
+        .map(|found| {
+            let path = found.path.display().to_string();
+            (path, found.line_number, found.line.as_str())
+        })
+        .collect();
+    assert_eq!(found, [(String::from("src/main.rs"), 2, "// Needle here")]);
+    assert_eq!(grep.files_searched.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn closing_grep_stops_the_search() {
+    let mut harness = Harness::new(&[]);
+    for number in 0..500 {
+        fs::write(harness.path(&number.to_string()), "needle\n").unwrap();
+    }
+
+    harness.type_keys(":grep needle\n\x1b");
+    assert_eq!(harness.app.mode, Mode::Browse);
+    wait_for_grep(&mut harness);
+
+    harness.type_keys(":grep\n");
+    assert_eq!(harness.app.mode, Mode::Grep);
+    let grep = harness.app.grep.as_ref().unwrap();
+    assert!(grep.files_searched.load(Ordering::Relaxed) < 500);
+}
@@ -0,0 +1,179 @@
+use std::fs;
+
+use termion::event::Key;
+
+use super::{Harness, HEIGHT};
+use crate::app::Mode;
+use crate::history::{History, MAX_HISTORY};
+
+fn line(harness: &Harness) -> (String, usize) {
+    (
+        harness.app.command_line.text(),
+        harness.app.command_line.cursor,
+    )
+}
+
+#[test]
+fn the_cursor_moves_and_text_goes_in_where_it_is() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":dr one");
+    harness.press(Key::Home);
+    harness.press(Key::Right);
+    harness.type_keys("i");
+    assert_eq!(line(&harness), (String::from("dir one"), 2));
+
+    harness.press(Key::End);
+    harness.press(Key::Alt('b'));
+    harness.press(Key::Delete);
+    harness.type_keys("O");
+    assert_eq!(line(&harness), (String::from("dir One"), 5));
+
+    harness.type_keys("\n");
+    assert!(harness.path("One").is_dir());
+}
+
+#[test]
+fn killed_text_can_be_yanked_back() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":rename some/long/path");
+    harness.press(Key::Alt('\x7f'));
+    assert_eq!(line(&harness).0, "rename some/long/");
+    harness.press(Key::Ctrl('w'));
+    assert_eq!(line(&harness).0, "rename ");
+
+    harness.press(Key::Ctrl('a'));
+    harness.press(Key::Ctrl('k'));
+    assert!(harness.app.command_line.is_empty());
+    harness.press(Key::Ctrl('y'));
+    assert_eq!(line(&harness), (String::from("rename "), 7));
+
+    harness.press(Key::Left);
+    harness.press(Key::Ctrl('u'));
+    assert_eq!(line(&harness), (String::from(" "), 0));
+    harness.press(Key::Alt('d'));
+    assert_eq!(line(&harness).0, "");
+}
+
+#[test]
+fn backspace_deletes_every_character_then_leaves() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":ab\x7f\x7f");
+    assert_eq!(harness.app.mode, Mode::Command);
+    assert!(harness.app.command_line.is_empty());
+
+    harness.type_keys("\x7f");
+    assert_eq!(harness.app.mode, Mode::Browse);
+}
+
+#[test]
+fn enter_on_an_empty_line_does_nothing() {
+    let mut harness = Harness::new(&["file"]);
+
+    harness.type_keys(":  \n");
+    assert_eq!(harness.app.mode, Mode::Browse);
+    assert!(harness.message().is_none());
+    assert!(harness.app.history.entries.is_empty());
+}
+
+#[test]
+fn up_and_down_go_through_earlier_commands() {
+    let mut harness = Harness::new(&["file"]);
+    harness.type_keys(":sort size\n:hidden\n:hidden\n");
+
+    harness.type_keys(":sor");
+    harness.press(Key::Up);
+    assert_eq!(line(&harness), (String::from("hidden"), 6));
+    harness.press(Key::Up);
+    assert_eq!(line(&harness).0, "sort size");
+    harness.press(Key::Up);
+    assert_eq!(line(&harness).0, "sort size");
+
+    harness.press(Key::Down);
+    assert_eq!(line(&harness).0, "hidden");
+    harness.press(Key::Down);
+    assert_eq!(line(&harness).0, "sor");
+    assert_eq!(harness.app.history.entries, ["sort size", "hidden"]);
+}
+
+#[test]
+fn ctrl_r_finds_older_commands_containing_the_text() {
+    let mut harness = Harness::new(&["file"]);
+    harness.type_keys(":filter *.rs\n:sort size\n:filter *.md\n:filter\n");
+
+    harness.type_keys(":");
+    harness.press(Key::Ctrl('r'));
+    harness.type_keys("filter ");
+    let found = |harness: &Harness| {
+        let search = harness.app.history_search.as_ref().unwrap();
+        search.matched(&harness.app.history).map(String::from)
+    };
+    assert_eq!(found(&harness).as_deref(), Some("filter *.md"));
+    harness.press(Key::Ctrl('r'));
+    assert_eq!(found(&harness).as_deref(), Some("filter *.rs"));
+    harness.press(Key::Ctrl('r'));
+    assert_eq!(found(&harness).as_deref(), Some("filter *.rs"));
+
+    harness.press(Key::End);
+    assert!(harness.app.history_search.is_none());
+    assert_eq!(line(&harness).0, "filter *.rs");
+
+    harness.press(Key::Ctrl('r'));
+    harness.type_keys("sort\x1b");
+    assert!(harness.app.history_search.is_none());
+    assert_eq!(line(&harness).0, "filter *.rs");
+}
+
+#[test]
+fn history_is_kept_in_a_file_between_sessions() {
+    let harness = Harness::new(&[]);
+    let path = harness.path("tfex/history");
+
+    let mut history = History::load(path.clone()).unwrap();
+    assert!(history.entries.is_empty());
+    history.add("sort size").unwrap();
+    history.add("sort size").unwrap();
+    history.add(" ").unwrap();
+    history.add("!make").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "sort size\n!make\n");
+
+    let commands: Vec<String> = (0..MAX_HISTORY + 5).map(|n| n.to_string()).collect();
+    fs::write(&path, commands.join("\n")).unwrap();
+    let history = History::load(path.clone()).unwrap();
+    assert_eq!(history.entries.len(), MAX_HISTORY);
+    assert_eq!(history.entries[0], "5");
+    assert_eq!(
+        fs::read_to_string(&path).unwrap().lines().count(),
+        MAX_HISTORY
+    );
+}
+
+#[test]
+fn the_cursor_is_drawn_where_the_line_is_edited() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":sort");
+    harness.press(Key::Left);
+    let screen = harness.screen();
+    assert!(screen[HEIGHT as usize - 2].starts_with("│:sort "));
+    assert_eq!(harness.terminal.get_cursor().unwrap(), (5, HEIGHT - 2));
+
+    //A line too long for the box scrolls to keep the cursor in view
+    harness.type_keys(&"x".repeat(100));
+    let screen = harness.screen();
+    assert!(screen[HEIGHT as usize - 2].contains(&"x".repeat(60)));
+    assert!(!screen[HEIGHT as usize - 2].contains(":sor"));
+    assert_eq!(harness.terminal.get_cursor().unwrap(), (78, HEIGHT - 2));
+}
+
+#[test]
+fn typing_a_filter_narrows_the_listing_as_it_goes() {
+    let mut harness = Harness::new(&["main.rs", "lib.rs", "notes.md"]);
+
+    harness.type_keys(":filter *.rs");
+    assert_eq!(harness.listing(), ["lib.rs", "main.rs"]);
+    harness.press(Key::Esc);
+    assert_eq!(harness.listing().len(), 3);
+}
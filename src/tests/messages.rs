@@ -0,0 +1,121 @@
+use std::error::Error as _;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use super::Harness;
+use crate::error::Error;
+use crate::file_ops;
+use crate::messages::{Messages, Severity};
+
+#[test]
+fn messages_time_out_once_drawn_and_errors_stay_longest() {
+    let mut messages = Messages::default();
+
+    messages.info("done");
+    assert_eq!(messages.time_left(), None);
+    messages.drawn();
+    let info = messages.time_left().unwrap();
+    assert!(!messages.expire());
+
+    messages.error("failed");
+    assert_eq!(messages.current().unwrap().severity, Severity::Error);
+    messages.drawn();
+    let error = messages.time_left().unwrap();
+    assert!(info < error && error <= Duration::from_secs(5));
+}
+
+#[test]
+fn the_loop_wakes_up_to_clear_a_message() {
+    let mut harness = Harness::new(&[]);
+    assert_eq!(harness.app.next_timeout(), None);
+
+    harness.type_keys(":nonsense\n");
+    harness.screen();
+    assert!(harness.app.next_timeout().is_some());
+}
+
+#[test]
+fn the_messages_view_lists_the_whole_session() {
+    let mut harness = Harness::new(&["file"]);
+
+    harness.type_keys(":sort\n:nonsense\n:rename\n:messages\n");
+    let view = harness.app.text_view.as_ref().unwrap();
+    assert_eq!(view.title, "Messages");
+    let lines: Vec<&str> = view.lines.iter().map(|line| &line[9..]).collect();
+    assert_eq!(
+        lines,
+        [
+            "Info    Sorted by name↑, dirs first",
+            "Warning Not a command: nonsense",
+            "Warning Usage: :rename <new name>",
+        ]
+    );
+}
+
+#[test]
+fn file_errors_say_what_failed_and_where() {
+    let harness = Harness::new(&["taken/"]);
+    let command = [String::from("dir"), String::from("taken")];
+
+    let err = file_ops::create_directory(&command, harness.dir.path()).unwrap_err();
+    match &err {
+        Error::Io {
+            operation, source, ..
+        } => {
+            assert_eq!(*operation, "create");
+            assert_eq!(source.kind(), io::ErrorKind::AlreadyExists);
+        }
+        other => panic!("expected an io error, got {:?}", other),
+    }
+    assert_eq!(err.severity(), Severity::Error);
+    assert!(err.to_string().starts_with(&format!(
+        "Couldn't create {}: ",
+        harness.path("taken").display()
+    )));
+    let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(source.kind(), io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn undo_errors_say_which_entry_is_in_the_way() {
+    let mut harness = Harness::new(&["old"]);
+
+    harness.type_keys("u");
+    assert_eq!(harness.message(), Some("Nothing to undo"));
+    assert_eq!(
+        harness.app.messages.current().unwrap().severity,
+        Severity::Warning
+    );
+
+    harness.type_keys(":rename new\n");
+    fs::write(harness.path("old"), "in the way").unwrap();
+    let err = harness.app.journal.undo().unwrap_err();
+    match &err {
+        Error::Stale { path, reason, .. } => {
+            assert_eq!(path, &harness.path("old"));
+            assert_eq!(*reason, "already exists");
+        }
+        other => panic!("expected a stale error, got {:?}", other),
+    }
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Can't undo rename 'old' to 'new': {} already exists",
+            harness.path("old").display()
+        )
+    );
+    assert_eq!(harness.app.journal.undo.len(), 1);
+}
+
+#[test]
+fn renaming_several_entries_is_refused() {
+    let mut harness = Harness::new(&["a", "b"]);
+
+    harness.type_keys(" j :rename c\n");
+    assert_eq!(
+        harness.message(),
+        Some("Can't rename more than one entry at once")
+    );
+    assert!(harness.path("a").exists() && harness.path("b").exists());
+}
@@ -0,0 +1,184 @@
+//Tests that drive the app the way the main loop does, with keys typed by the test, a temporary
+//directory to work in and a TestBackend in place of the terminal
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+use termion::event::Key;
+use tui::backend::TestBackend;
+use tui::Terminal;
+
+use crate::app::App;
+use crate::config::Config;
+use crate::events::{self, Event};
+use crate::keys;
+use crate::trash::Trash;
+
+mod commands;
+mod file_operations;
+mod grep;
+mod line_editing;
+mod messages;
+mod navigation;
+mod parsing;
+mod rendering;
+mod trash;
+#[cfg(target_os = "linux")]
+mod watching;
+
+const WIDTH: u16 = 80;
+const HEIGHT: u16 = 24;
+//How long to wait for background work before failing
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Harness {
+    pub app: App,
+    pub terminal: Terminal<TestBackend>,
+    events: Receiver<Event>,
+    //Kept open so the waker's writes go somewhere
+    _pipe: File,
+    //Removed when the harness is dropped
+    pub dir: TempDir,
+    //Holds the app's trash, kept apart from `dir` so it isn't in the listing
+    pub trash_dir: TempDir,
+}
+
+//Nothing asks for confirmation, and previews aren't highlighted, which would load the syntaxes
+pub fn test_config() -> Config {
+    Config {
+        confirm: HashSet::new(),
+        theme: None,
+        ..Config::default()
+    }
+}
+
+impl Harness {
+    //A temporary directory with `entries` in it. Names ending in '/' are made as directories, and
+    //files hold their own name.
+    pub fn new(entries: &[&str]) -> Harness {
+        Harness::with_config(entries, test_config())
+    }
+
+    pub fn with_config(entries: &[&str], config: Config) -> Harness {
+        let dir = tempfile::tempdir().unwrap();
+        for entry in entries {
+            let path = dir.path().join(entry);
+            if entry.ends_with('/') {
+                fs::create_dir_all(&path).unwrap();
+            } else {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).unwrap();
+                }
+                fs::write(&path, entry).unwrap();
+            }
+        }
+
+        let (waker, pipe, events) = events::channel().unwrap();
+        let mut app = App::new(dir.path().to_path_buf(), None, config, waker, HEIGHT).unwrap();
+        let trash_dir = tempfile::tempdir().unwrap();
+        app.trash = Some(Trash::at(&trash_dir.path().join("Trash")));
+
+        Harness {
+            app,
+            terminal: Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap(),
+            events,
+            _pipe: pipe,
+            dir,
+            trash_dir,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn press(&mut self, key: Key) {
+        keys::handle_key(&mut self.app, key).unwrap();
+        self.app.update().unwrap();
+    }
+
+    //Types each character as a key: '\n' is Enter, '\x1b' is Esc and '\x7f' is Backspace
+    pub fn type_keys(&mut self, keys: &str) {
+        for character in keys.chars() {
+            self.press(match character {
+                '\x1b' => Key::Esc,
+                '\x7f' => Key::Backspace,
+                character => Key::Char(character),
+            });
+        }
+    }
+
+    //Names in the listing, in order
+    pub fn listing(&self) -> Vec<String> {
+        self.app
+            .directory_contents
+            .iter()
+            .map(|item| item.name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    //The text of the message on screen, if there is one
+    pub fn message(&self) -> Option<&str> {
+        self.app
+            .messages
+            .current()
+            .map(|message| message.text.as_str())
+    }
+
+    pub fn selected(&self) -> Option<String> {
+        self.app
+            .selection_index
+            .and_then(|index| self.app.directory_contents.get(index))
+            .map(|item| item.name().to_string_lossy().into_owned())
+    }
+
+    //Selects the entry named `name` in the listing
+    pub fn select(&mut self, name: &str) {
+        assert!(
+            self.app.select_file_named(OsStr::new(name)),
+            "no entry {}",
+            name
+        );
+    }
+
+    pub fn wait_for_jobs(&mut self) {
+        let started = Instant::now();
+        while !self.app.jobs.jobs.is_empty() {
+            assert!(started.elapsed() < TIMEOUT, "jobs didn't finish");
+            thread::sleep(Duration::from_millis(10));
+            self.app.update().unwrap();
+        }
+    }
+
+    //Passes file system events to the app, as the main loop does, until `done` is true
+    pub fn wait_for_changes(&mut self, done: impl Fn(&Harness) -> bool) {
+        let started = Instant::now();
+        while !done(self) {
+            assert!(started.elapsed() < TIMEOUT, "changes didn't show up");
+            if let Ok(Event::FileSystem(changes)) =
+                self.events.recv_timeout(Duration::from_millis(50))
+            {
+                self.app.apply_changes(changes).unwrap();
+            }
+            self.app.update().unwrap();
+        }
+    }
+
+    //What's on screen, one string a row
+    pub fn screen(&mut self) -> Vec<String> {
+        crate::ui::draw(&mut self.terminal, &mut self.app).unwrap();
+        let buffer = self.terminal.backend().buffer();
+        (0..HEIGHT)
+            .map(|y| {
+                (0..WIDTH)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+
+use termion::event::Key;
+
+use super::{Harness, HEIGHT};
+use crate::app::Mode;
+
+#[test]
+fn lists_directories_first_then_files_by_name() {
+    let harness = Harness::new(&["b.txt", "zdir/", "a.txt", "adir/"]);
+
+    assert_eq!(harness.listing(), ["adir", "zdir", "a.txt", "b.txt"]);
+    assert_eq!(harness.selected().as_deref(), Some("adir"));
+}
+
+#[test]
+fn j_and_k_move_the_selection_and_stop_at_the_ends() {
+    let mut harness = Harness::new(&["a", "b", "c"]);
+
+    harness.type_keys("jj");
+    assert_eq!(harness.selected().as_deref(), Some("c"));
+    harness.type_keys("j");
+    assert_eq!(harness.selected().as_deref(), Some("c"));
+
+    harness.type_keys("kkk");
+    assert_eq!(harness.selected().as_deref(), Some("a"));
+}
+
+#[test]
+fn enter_opens_a_directory_and_backspace_goes_back_up() {
+    let mut harness = Harness::new(&["dir/inner.txt", "top.txt"]);
+
+    harness.type_keys("\n");
+    assert_eq!(harness.app.current_directory, harness.path("dir"));
+    assert_eq!(harness.listing(), ["inner.txt"]);
+
+    harness.press(Key::Backspace);
+    assert_eq!(harness.app.current_directory, harness.dir.path());
+    assert_eq!(harness.listing(), ["dir", "top.txt"]);
+}
+
+#[test]
+fn going_up_to_a_directory_that_cant_be_read_stays_put() {
+    let mut harness = Harness::new(&["file"]);
+    //As if the parent had gone, which can't be done to a directory that's still there
+    let directory = harness.path("gone/dir");
+    harness.app.current_directory = directory.clone();
+
+    harness.press(Key::Backspace);
+    assert_eq!(harness.app.current_directory, directory);
+    let expected = format!("Couldn't open {}: ", harness.path("gone").display());
+    assert!(harness.message().unwrap().starts_with(&expected));
+}
+
+#[test]
+fn period_hides_and_shows_dotfiles() {
+    let mut harness = Harness::new(&[".hidden", "shown"]);
+    assert_eq!(harness.listing(), [".hidden", "shown"]);
+
+    harness.type_keys(".");
+    assert_eq!(harness.listing(), ["shown"]);
+
+    harness.type_keys(".");
+    assert_eq!(harness.listing(), [".hidden", "shown"]);
+}
+
+#[test]
+fn search_selects_the_first_match_and_n_wraps_around() {
+    let mut harness = Harness::new(&["apple", "banana", "cherry", "grape"]);
+
+    harness.type_keys("/ap");
+    assert_eq!(harness.app.mode, Mode::Search);
+    assert_eq!(harness.selected().as_deref(), Some("apple"));
+
+    harness.type_keys("\n");
+    assert_eq!(harness.app.mode, Mode::Browse);
+    harness.type_keys("n");
+    assert_eq!(harness.selected().as_deref(), Some("grape"));
+    harness.type_keys("n");
+    assert_eq!(harness.selected().as_deref(), Some("apple"));
+    harness.type_keys("N");
+    assert_eq!(harness.selected().as_deref(), Some("grape"));
+}
+
+#[test]
+fn cancelling_a_search_goes_back_to_where_it_started() {
+    let mut harness = Harness::new(&["apple", "banana", "cherry"]);
+    harness.type_keys("j");
+
+    harness.type_keys("/cher\x1b");
+    assert_eq!(harness.selected().as_deref(), Some("banana"));
+    assert!(harness.app.search.is_none());
+}
+
+#[test]
+fn backspace_on_an_empty_search_leaves_it() {
+    let mut harness = Harness::new(&["apple", "banana", "cherry"]);
+    harness.type_keys("j");
+
+    harness.type_keys("/ch");
+    assert!(harness.screen()[HEIGHT as usize - 2].starts_with("│/ch "));
+    harness.type_keys("\x7f\x7f");
+    assert_eq!(harness.app.mode, Mode::Search);
+    assert!(harness.screen()[HEIGHT as usize - 2].starts_with("│/ "));
+
+    harness.type_keys("\x7f");
+    assert_eq!(harness.app.mode, Mode::Browse);
+    assert_eq!(harness.selected().as_deref(), Some("banana"));
+}
+
+#[test]
+fn filter_narrows_the_listing_until_cleared() {
+    let mut harness = Harness::new(&["main.rs", "lib.rs", "README.md", "src/"]);
+
+    harness.type_keys(":filter *.rs\n");
+    assert_eq!(harness.listing(), ["lib.rs", "main.rs"]);
+
+    harness.type_keys(":filter /^read/\n");
+    assert_eq!(harness.listing(), ["README.md"]);
+
+    harness.type_keys(":filter\n");
+    assert_eq!(harness.listing().len(), 4);
+}
+
+#[test]
+fn visual_selection_marks_a_range() {
+    let mut harness = Harness::new(&["a", "b", "c", "d"]);
+
+    harness.type_keys("jVjj ");
+    let marked: Vec<bool> = (0..4).map(|index| harness.app.is_marked(index)).collect();
+    assert_eq!(marked, [false, true, true, true]);
+
+    harness.press(Key::Esc);
+    assert!(harness.app.marked_files.is_empty());
+}
+
+#[test]
+fn gitignore_files_are_followed_outside_of_git_repositories() {
+    let mut harness = Harness::new(&["build/out", "debug.log", "main.rs"]);
+    std::fs::write(harness.path(".gitignore"), "*.log\nbuild/\n").unwrap();
+    harness.app.refresh().unwrap();
+    assert_eq!(
+        harness.listing(),
+        ["build", ".gitignore", "debug.log", "main.rs"]
+    );
+
+    harness.type_keys(":gitignore\n");
+    assert_eq!(harness.listing(), [".gitignore", "main.rs"]);
+}
+
+#[test]
+fn a_deleted_directory_is_reported_once_with_where_it_went() {
+    let mut harness = Harness::new(&["dir/inner/deepest/"]);
+    harness.type_keys("\n\n\n");
+    assert_eq!(
+        harness.app.current_directory,
+        harness.path("dir/inner/deepest")
+    );
+
+    fs::remove_dir_all(harness.path("dir")).unwrap();
+    harness.app.refresh().unwrap();
+    assert_eq!(harness.app.current_directory, harness.dir.path());
+    assert_eq!(harness.app.messages.history.len(), 1);
+    assert!(harness
+        .message()
+        .unwrap()
+        .ends_with(&format!(". Went up to {}", harness.dir.path().display())));
+}
@@ -0,0 +1,138 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use super::Harness;
+use crate::args;
+use crate::config::{self, Operation};
+use crate::opener;
+use crate::pattern::Pattern;
+use crate::shell::{self, Placeholders};
+
+fn matches(pattern: &str, name: &str) -> bool {
+    Pattern::parse(pattern).unwrap().is_match(OsStr::new(name))
+}
+
+#[test]
+fn globs_without_wildcards_match_anywhere_in_the_name() {
+    assert!(matches("main", "src_main.rs"));
+    assert!(matches("*.rs", "lib.rs"));
+    assert!(!matches("*.rs", "lib.rs.bak"));
+}
+
+#[test]
+fn patterns_are_smart_case() {
+    assert!(matches("readme", "README.md"));
+    assert!(!matches("Readme", "README.md"));
+    assert!(matches("/^read/", "README.md"));
+    assert!(!matches("/^Read/", "README.md"));
+}
+
+#[test]
+fn bad_regexes_are_rejected() {
+    assert!(Pattern::parse("/(/").is_err());
+    assert_eq!(Pattern::parse("/a+/").unwrap().text, "/a+/");
+}
+
+#[test]
+fn placeholders_expand_to_quoted_paths() {
+    let placeholders = Placeholders {
+        selected: Some(PathBuf::from("/tmp/a b")),
+        targets: vec![PathBuf::from("/tmp/a b"), PathBuf::from("/tmp/it's")],
+        directory: PathBuf::from("/tmp"),
+    };
+
+    assert_eq!(
+        shell::expand("cp %F %d/backup", &placeholders).unwrap(),
+        "cp '/tmp/a b' '/tmp/it'\\''s' '/tmp'/backup"
+    );
+    assert_eq!(
+        shell::expand("date +%Y 100%% %f", &placeholders).unwrap(),
+        "date +%Y 100% '/tmp/a b'"
+    );
+}
+
+#[test]
+fn placeholders_need_something_selected() {
+    let placeholders = Placeholders {
+        selected: None,
+        targets: Vec::new(),
+        directory: PathBuf::from("/tmp"),
+    };
+
+    assert!(shell::expand("echo %f", &placeholders).is_err());
+    assert!(shell::expand("echo %F", &placeholders).is_err());
+    assert_eq!(shell::quote(OsStr::new("''")), "''\\'''\\'''");
+}
+
+#[test]
+fn config_options_are_read() {
+    let config = config::parse(
+        "# comment\n\
+         confirm = delete, purge\n\
+         show_hidden = false\n\
+         \n\
+         theme = none\n",
+    )
+    .unwrap();
+
+    assert!(config.confirms(Operation::Delete));
+    assert!(config.confirms(Operation::Purge));
+    assert!(!config.confirms(Operation::EmptyTrash));
+    assert!(!config.show_hidden);
+    assert!(config.theme.is_none());
+}
+
+#[test]
+fn config_errors_name_the_line() {
+    let err = |contents| config::parse(contents).err().unwrap();
+
+    assert_eq!(err("\nnonsense"), "line 2: expected 'key = value'");
+    assert_eq!(err("colour = red"), "line 1: unknown option 'colour'");
+    assert_eq!(
+        err("confirm = explode"),
+        "line 1: unknown operation 'explode'"
+    );
+    assert!(err("open ext = zathura").starts_with("line 1: "));
+    assert!(err("open ext pdf =").contains("expected a command"));
+}
+
+#[test]
+fn openers_use_the_first_matching_rule() {
+    let rules = vec![
+        opener::parse_rule("ext pdf,djvu", "zathura &").unwrap(),
+        opener::parse_rule("glob *.log", "less +F %f").unwrap(),
+    ];
+
+    let pdf = opener::opener_for(Path::new("/tmp/Paper.PDF"), &rules);
+    assert_eq!(pdf.command.get_program(), "zathura");
+    assert!(pdf.background);
+    assert_eq!(
+        pdf.command.get_args().collect::<Vec<_>>(),
+        ["/tmp/Paper.PDF"]
+    );
+
+    let log = opener::opener_for(Path::new("/tmp/app.log"), &rules);
+    assert_eq!(log.command.get_program(), "less");
+    assert!(!log.background);
+    assert_eq!(
+        log.command.get_args().collect::<Vec<_>>(),
+        ["+F", "/tmp/app.log"]
+    );
+}
+
+#[test]
+fn everything_after_a_double_dash_is_the_path() {
+    let harness = Harness::new(&["--help"]);
+    let parse = |arguments: &[&OsStr]| args::parse(arguments.iter().map(OsString::from));
+    let file = harness.path("--help");
+
+    let parsed = parse(&[OsStr::new("--"), file.as_os_str()]).unwrap();
+    assert!(!parsed.help);
+    assert_eq!(parsed.select.as_deref(), Some(OsStr::new("--help")));
+
+    //Options after it are paths too, and only one path can be given
+    let err = parse(&[OsStr::new("--"), file.as_os_str(), OsStr::new("--help")]);
+    assert_eq!(err.err().as_deref(), Some("Only one path can be given"));
+    let err = parse(&[OsStr::new("--"), OsStr::new("-s"), file.as_os_str()]);
+    assert_eq!(err.err().as_deref(), Some("Only one path can be given"));
+}
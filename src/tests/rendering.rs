@@ -0,0 +1,84 @@
+use tui::backend::TestBackend;
+use tui::Terminal;
+
+use super::{test_config, Harness};
+use crate::config::{Config, Operation};
+
+#[test]
+fn the_listing_shows_the_directory_and_its_entries() {
+    let mut harness = Harness::new(&["folder/", "notes.txt"]);
+
+    let screen = harness.screen();
+    assert!(screen[0].contains(&harness.dir.path().display().to_string()));
+    assert!(screen[1].contains("folder"));
+    assert!(screen[2].contains("notes.txt"));
+}
+
+#[test]
+fn messages_replace_the_command_box_titled_by_severity() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":nonsense\n");
+    let screen = harness.screen();
+    assert!(screen.iter().any(|row| row.contains("Warning")));
+    assert!(screen
+        .iter()
+        .any(|row| row.contains("Not a command: nonsense")));
+
+    harness.type_keys(":sort\n");
+    let screen = harness.screen();
+    assert!(screen.iter().any(|row| row.contains("Info")));
+    assert!(screen.iter().any(|row| row.contains("Sorted by name")));
+}
+
+#[test]
+fn a_search_or_selection_shows_over_a_message() {
+    let mut harness = Harness::new(&["apple", "banana"]);
+
+    harness.type_keys("/zzz\nn");
+    assert_eq!(harness.message(), Some("Pattern not found: zzz"));
+    harness.type_keys("/ban");
+    let screen = harness.screen();
+    assert!(screen.iter().any(|row| row.contains("/ban")));
+    assert!(!screen.iter().any(|row| row.contains("Pattern not found")));
+
+    harness.type_keys("\x1bV");
+    assert!(harness.message().is_some());
+    let screen = harness.screen();
+    assert!(screen.iter().any(|row| row.contains("-- SELECT --")));
+}
+
+#[test]
+fn the_command_box_shows_what_is_typed() {
+    let mut harness = Harness::new(&[]);
+
+    harness.type_keys(":sort size");
+    let screen = harness.screen();
+    assert!(screen.iter().any(|row| row.contains(":sort size")));
+}
+
+#[test]
+fn tiny_terminals_draw_without_panicking() {
+    let config = Config {
+        confirm: [Operation::Purge].iter().cloned().collect(),
+        ..test_config()
+    };
+    let mut harness = Harness::with_config(&["dir/", "a", "b", "c"], config);
+    let draw_tiny = |harness: &mut Harness| {
+        for &(width, height) in &[(20, 4), (20, 3), (10, 2), (3, 1), (1, 1), (0, 0)] {
+            harness.terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            harness.app.set_terminal_height(height);
+            crate::ui::draw(&mut harness.terminal, &mut harness.app).unwrap();
+        }
+    };
+
+    draw_tiny(&mut harness);
+    harness.type_keys("p");
+    draw_tiny(&mut harness);
+    harness.type_keys(":sort size");
+    draw_tiny(&mut harness);
+    harness.type_keys("\n:messages\n");
+    draw_tiny(&mut harness);
+    harness.type_keys("q:purge\n");
+    draw_tiny(&mut harness);
+}
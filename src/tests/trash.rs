@@ -0,0 +1,141 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::symlink;
+
+use termion::event::Key;
+
+use super::Harness;
+use crate::trash::Trash;
+
+//Names in the trash's files/ directory, sorted
+fn trashed(harness: &Harness) -> Vec<String> {
+    let trash = harness.app.trash.as_ref().unwrap();
+    let mut names: Vec<String> = fs::read_dir(trash.files_directory())
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+fn info_files(harness: &Harness) -> usize {
+    let trash = harness.app.trash.as_ref().unwrap();
+    fs::read_dir(trash.directory.join("info"))
+        .map(|entries| entries.count())
+        .unwrap_or(0)
+}
+
+#[test]
+fn delete_moves_to_the_trash_and_restore_puts_it_back() {
+    let mut harness = Harness::new(&["a", "b"]);
+
+    harness.select("a");
+    harness.type_keys(":delete\n");
+    harness.wait_for_jobs();
+    assert_eq!(harness.listing(), ["b"]);
+    assert_eq!(trashed(&harness), ["a"]);
+    assert_eq!(info_files(&harness), 1);
+
+    harness.type_keys(":trash\n");
+    let files = harness.app.trash.as_ref().unwrap().files_directory();
+    assert_eq!(harness.app.current_directory, files);
+    assert_eq!(harness.listing(), ["a"]);
+    assert_eq!(
+        harness.app.trash_info[OsStr::new("a")].original_path,
+        harness.path("a")
+    );
+
+    harness.type_keys(":restore\n");
+    assert_eq!(fs::read_to_string(harness.path("a")).unwrap(), "a");
+    assert!(trashed(&harness).is_empty());
+    assert_eq!(info_files(&harness), 0);
+}
+
+#[test]
+fn restore_only_works_in_the_trash() {
+    let mut harness = Harness::new(&["a"]);
+
+    harness.type_keys(":restore\n");
+    assert_eq!(
+        harness.message(),
+        Some("Open the trash with :trash to restore files")
+    );
+    assert!(harness.path("a").exists());
+}
+
+#[test]
+fn deleting_in_the_trash_deletes_for_good() {
+    let mut harness = Harness::new(&["a", "b"]);
+
+    harness.type_keys(":delete\n");
+    harness.wait_for_jobs();
+    harness.type_keys(":trash\n:delete\n");
+    harness.wait_for_jobs();
+    assert!(trashed(&harness).is_empty());
+    assert_eq!(info_files(&harness), 0);
+    assert!(!harness.path("a").exists());
+}
+
+#[test]
+fn emptytrash_deletes_everything_in_the_trash() {
+    let mut harness = Harness::new(&["a", "b", "c"]);
+
+    harness.type_keys(":delete\n");
+    harness.wait_for_jobs();
+    harness.type_keys(":delete\n");
+    harness.wait_for_jobs();
+    assert_eq!(trashed(&harness), ["a", "b"]);
+
+    harness.type_keys(":emptytrash\n");
+    harness.wait_for_jobs();
+    assert!(trashed(&harness).is_empty());
+    assert_eq!(info_files(&harness), 0);
+    assert_eq!(harness.listing(), ["c"]);
+}
+
+#[test]
+fn trashing_can_be_undone_and_redone() {
+    let mut harness = Harness::new(&["a", "b"]);
+
+    harness.type_keys(":delete\n");
+    harness.wait_for_jobs();
+    assert_eq!(trashed(&harness), ["a"]);
+
+    harness.press(Key::Char('u'));
+    assert_eq!(harness.message(), Some("Undid trash 'a'"));
+    assert_eq!(fs::read_to_string(harness.path("a")).unwrap(), "a");
+    assert!(trashed(&harness).is_empty());
+    assert_eq!(info_files(&harness), 0);
+
+    harness.press(Key::Ctrl('r'));
+    assert_eq!(harness.message(), Some("Redid trash 'a'"));
+    assert!(!harness.path("a").exists());
+    assert_eq!(trashed(&harness), ["a"]);
+    assert_eq!(info_files(&harness), 1);
+}
+
+#[test]
+fn a_trash_reached_through_a_symlink_is_still_the_trash() {
+    let mut harness = Harness::new(&["a", "b"]);
+    let real = harness.trash_dir.path().join("real");
+    fs::create_dir(&real).unwrap();
+    symlink(&real, harness.trash_dir.path().join("link")).unwrap();
+    harness.app.trash = Some(Trash::at(&harness.trash_dir.path().join("link/Trash")));
+
+    harness.type_keys(":delete\n");
+    harness.wait_for_jobs();
+    harness.type_keys(":trash\n");
+    assert_eq!(
+        harness.app.current_directory,
+        real.canonicalize().unwrap().join("Trash/files")
+    );
+    assert_eq!(harness.listing(), ["a"]);
+    assert!(harness.app.trash_info.contains_key(OsStr::new("a")));
+
+    harness.type_keys(":restore\n");
+    assert!(harness.path("a").exists());
+}
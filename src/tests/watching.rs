@@ -0,0 +1,61 @@
+use std::fs;
+use std::sync::Arc;
+
+use super::Harness;
+use crate::events;
+use crate::watch::Watcher;
+
+#[test]
+fn files_made_elsewhere_show_up() {
+    let mut harness = Harness::new(&["a"]);
+
+    fs::write(harness.path("b"), "").unwrap();
+    fs::create_dir(harness.path("dir")).unwrap();
+    harness.wait_for_changes(|harness| harness.listing() == ["dir", "a", "b"]);
+}
+
+#[test]
+fn the_selection_follows_a_renamed_entry() {
+    let mut harness = Harness::new(&["a", "b", "c"]);
+    harness.select("b");
+
+    fs::rename(harness.path("b"), harness.path("z")).unwrap();
+    harness.wait_for_changes(|harness| harness.listing() == ["a", "c", "z"]);
+    assert_eq!(harness.selected().as_deref(), Some("z"));
+}
+
+#[test]
+fn deleting_the_selected_entry_keeps_the_selection_in_the_listing() {
+    let mut harness = Harness::new(&["a", "b"]);
+    harness.select("b");
+
+    fs::remove_file(harness.path("b")).unwrap();
+    harness.wait_for_changes(|harness| harness.listing() == ["a"]);
+    assert_eq!(harness.selected().as_deref(), Some("a"));
+}
+
+#[test]
+fn a_deleted_directory_is_left_for_its_parent() {
+    let mut harness = Harness::new(&["dir/inner/"]);
+    harness.type_keys("\n\n");
+    assert_eq!(harness.app.current_directory, harness.path("dir/inner"));
+
+    fs::remove_dir_all(harness.path("dir")).unwrap();
+    harness.wait_for_changes(|harness| harness.app.current_directory == harness.dir.path());
+    assert!(harness
+        .message()
+        .unwrap()
+        .contains("can't be read any more"));
+}
+
+#[test]
+fn dropping_the_watcher_stops_its_thread_and_closes_inotify() {
+    let harness = Harness::new(&["a"]);
+    let (waker, _pipe, _events) = events::channel().unwrap();
+    let mut watcher = Watcher::new(waker).unwrap();
+    assert!(watcher.watch_listed(harness.dir.path()));
+
+    let inotify = Arc::downgrade(&watcher.inotify);
+    drop(watcher);
+    assert!(inotify.upgrade().is_none());
+}
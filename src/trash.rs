@@ -0,0 +1,375 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+use crate::copy;
+use crate::error::Error;
+use crate::file_ops;
+use crate::jobs::{JobResult, Progress};
+
+//An item in the trash, read from its .trashinfo file
+pub struct TrashEntry {
+    pub name: OsString,
+    pub original_path: PathBuf,
+    pub deletion_date: String,
+}
+
+//A trash as described by the freedesktop.org trash spec, with the trashed items in files/ and
+//an info file for each in info/
+#[derive(Clone)]
+pub struct Trash {
+    //Canonicalised, so it can be compared with the directory being browsed
+    pub directory: PathBuf,
+}
+
+impl Trash {
+    //The home trash: $XDG_DATA_HOME/Trash, falling back to ~/.local/share/Trash
+    pub fn home() -> io::Result<Trash> {
+        Ok(Trash::at(&config::data_home()?.join("Trash")))
+    }
+
+    //The trash in `directory`, which doesn't have to exist yet
+    pub fn at(directory: &Path) -> Trash {
+        Trash {
+            directory: canonicalize_existing(directory),
+        }
+    }
+
+    //The trash `trashed` was moved into, given where it ended up
+    pub fn containing(trashed: &Path) -> io::Result<Trash> {
+        match trashed.parent().and_then(Path::parent) {
+            Some(directory) => Ok(Trash {
+                directory: directory.to_path_buf(),
+            }),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not in a trash",
+            )),
+        }
+    }
+
+    pub fn files_directory(&self) -> PathBuf {
+        self.directory.join("files")
+    }
+
+    fn info_directory(&self) -> PathBuf {
+        self.directory.join("info")
+    }
+
+    fn info_path(&self, name: &OsStr) -> PathBuf {
+        self.info_directory().join(info_file_name(name))
+    }
+
+    pub fn is_files_directory(&self, directory: &Path) -> bool {
+        self.files_directory() == directory
+    }
+}
+
+//Canonicalises as much of `path` as exists, keeping the rest as it is
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+fn info_file_name(name: &OsStr) -> OsString {
+    let mut file_name = name.to_os_string();
+    file_name.push(".trashinfo");
+    file_name
+}
+
+impl Trash {
+    //Moves every path into the trash on a job's worker thread
+    pub fn trash_paths(&self, paths: Vec<PathBuf>, progress: Arc<Progress>) -> JobResult {
+        let mut result = JobResult::default();
+
+        for path in &paths {
+            let size = file_ops::measure(path);
+            progress.add_totals(size.files, size.bytes);
+        }
+
+        for path in paths {
+            if progress.is_cancelled() {
+                result.failed.push(path);
+                continue;
+            }
+
+            match self.trash_path(&path, &progress) {
+                Ok(trashed) => result.done.push((path, trashed)),
+                Err(err) => {
+                    if !progress.is_cancelled() {
+                        result.errors.push(Error::io("trash", &path, err));
+                    }
+                    result.failed.push(path);
+                }
+            }
+        }
+
+        result
+    }
+
+    //Moves one item into the trash, returning where it ended up
+    pub fn trash_path(&self, path: &Path, progress: &Arc<Progress>) -> io::Result<PathBuf> {
+        let files = self.files_directory();
+        let info = self.info_directory();
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&files)?;
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&info)?;
+
+        if path.starts_with(&self.directory) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "already in the trash",
+            ));
+        }
+
+        let original_path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            env::current_dir()?.join(path)
+        };
+        let name = original_path
+            .file_name()
+            .map(OsString::from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "nothing to trash"))?;
+
+        //Creating the info file with create_new reserves the name, as the spec requires
+        let (trashed_name, mut info_file) = reserve_name(&files, &info, &name)?;
+        let deleted = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&original_path),
+            file_ops::format_iso_timestamp(deleted)
+        );
+        if let Err(err) = info_file.write_all(contents.as_bytes()) {
+            let _ = fs::remove_file(self.info_path(&trashed_name));
+            return Err(err);
+        }
+
+        //A rename when possible, otherwise a verified copy into the trash
+        let mut report = copy::CopyReport::new(Arc::clone(progress));
+        copy::move_path(
+            &original_path,
+            &files.join(&trashed_name),
+            false,
+            &mut report,
+        );
+
+        if let Some((_, err)) = report.errors.into_iter().next() {
+            let _ = fs::remove_file(self.info_path(&trashed_name));
+            return Err(err);
+        }
+        //A cancelled cross-device move leaves the original in place, so drop the partial copy
+        if progress.is_cancelled() && fs::symlink_metadata(&original_path).is_ok() {
+            let _ = file_ops::remove_path(&files.join(&trashed_name));
+            let _ = fs::remove_file(self.info_path(&trashed_name));
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+
+        Ok(files.join(&trashed_name))
+    }
+
+    pub fn list(&self) -> io::Result<Vec<TrashEntry>> {
+        let info = self.info_directory();
+        let mut entries: Vec<TrashEntry> = Vec::new();
+
+        let dir = match fs::read_dir(&info) {
+            Ok(dir) => dir,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(err) => return Err(err),
+        };
+
+        for item in dir.flatten() {
+            let file_name = item.file_name();
+            let name = match file_name.as_bytes().strip_suffix(b".trashinfo") {
+                Some(name) => OsStr::from_bytes(name),
+                None => continue,
+            };
+
+            if let Ok(entry) = self.read_info(name) {
+                entries.push(entry);
+            }
+        }
+
+        entries.sort_by(|a, b| b.deletion_date.cmp(&a.deletion_date));
+        Ok(entries)
+    }
+
+    pub fn read_info(&self, name: &OsStr) -> io::Result<TrashEntry> {
+        let contents = fs::read_to_string(self.info_path(name))?;
+        let mut original_path: Option<PathBuf> = None;
+        let mut deletion_date = String::new();
+
+        for line in contents.lines() {
+            if let Some(value) = line.strip_prefix("Path=") {
+                original_path = Some(decode_path(value));
+            } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+                deletion_date = value.to_string();
+            }
+        }
+
+        match original_path {
+            Some(original_path) => Ok(TrashEntry {
+                name: name.to_os_string(),
+                original_path,
+                deletion_date,
+            }),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trash info has no Path",
+            )),
+        }
+    }
+
+    //Moves a trashed item back to where it was deleted from
+    pub fn restore(&self, name: &OsStr) -> io::Result<PathBuf> {
+        let entry = self.read_info(name)?;
+        let trashed = self.files_directory().join(name);
+
+        if fs::symlink_metadata(&entry.original_path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} already exists",
+                    file_ops::display_path(&entry.original_path)
+                ),
+            ));
+        }
+
+        if let Some(parent) = entry.original_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut report = copy::CopyReport::default();
+        copy::move_path(&trashed, &entry.original_path, false, &mut report);
+        if let Some((_, err)) = report.errors.into_iter().next() {
+            return Err(err);
+        }
+
+        fs::remove_file(self.info_path(name))?;
+        Ok(entry.original_path)
+    }
+
+    //Permanently deletes trashed items along with their info files, on a job's worker thread
+    pub fn purge_paths(&self, paths: Vec<PathBuf>, progress: Arc<Progress>) -> JobResult {
+        let names: Vec<OsString> = paths
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(OsString::from)
+            .collect();
+
+        let result = file_ops::delete_paths(paths, progress);
+
+        for name in names {
+            if fs::symlink_metadata(self.files_directory().join(&name)).is_err() {
+                let _ = fs::remove_file(self.info_path(&name));
+            }
+        }
+
+        result
+    }
+
+    //Permanently deletes everything in the trash, on a job's worker thread
+    pub fn empty(&self, progress: Arc<Progress>) -> JobResult {
+        match self.contents() {
+            Ok(paths) => self.purge_paths(paths, progress),
+            Err(err) => JobResult::error(Error::io("read", self.files_directory(), err)),
+        }
+    }
+
+    //Every trashed item
+    pub fn contents(&self) -> io::Result<Vec<PathBuf>> {
+        let entries = fs::read_dir(self.files_directory())?;
+        Ok(entries.flatten().map(|entry| entry.path()).collect())
+    }
+}
+
+fn reserve_name(files: &Path, info: &Path, name: &OsStr) -> io::Result<(OsString, fs::File)> {
+    let mut candidate = name.to_os_string();
+    let mut number = 1;
+
+    loop {
+        if fs::symlink_metadata(files.join(&candidate)).is_err() {
+            let result = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(info.join(info_file_name(&candidate)));
+
+            match result {
+                Ok(file) => return Ok((candidate, file)),
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        number += 1;
+        candidate = name.to_os_string();
+        candidate.push(format!(".{}", number));
+    }
+}
+
+//Percent-encodes a path for the Path= key, leaving '/' and unreserved characters alone
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(*byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_path(value: &str) -> PathBuf {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    PathBuf::from(OsString::from_vec(decoded))
+}
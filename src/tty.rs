@@ -0,0 +1,36 @@
+use std::io::Stdout;
+use std::process::Command;
+
+use termion::raw::RawTerminal;
+use tui::backend::TermionBackend;
+use tui::Terminal;
+
+//Hands the terminal to `command` until it exits, e.g. for an editor, then takes it back and
+//redraws everything
+pub fn run_foreground(
+    terminal: &mut Terminal<TermionBackend<Stdout>>,
+    raw_mode: &RawTerminal<Stdout>,
+    command: &mut Command,
+) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+
+    terminal.clear().map_err(|err| err.to_string())?;
+    terminal.show_cursor().map_err(|err| err.to_string())?;
+    raw_mode.suspend_raw_mode().map_err(|err| err.to_string())?;
+
+    let status = command.status();
+
+    raw_mode
+        .activate_raw_mode()
+        .map_err(|err| err.to_string())?;
+    terminal.hide_cursor().map_err(|err| err.to_string())?;
+    //Resizing forgets what was drawn before, so the next frame is drawn in full
+    let size = terminal.size().map_err(|err| err.to_string())?;
+    terminal.resize(size).map_err(|err| err.to_string())?;
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{} exited with {}", program, status)),
+        Err(err) => Err(format!("Couldn't run {}: {}", program, err)),
+    }
+}
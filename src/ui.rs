@@ -1,122 +1,321 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::io;
-use std::path::PathBuf;
-use std::thread;
+use std::path::Path;
+use std::sync::atomic::Ordering;
 
 use tui::backend::Backend;
+use tui::buffer::Buffer;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
-use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
-use tui::Frame;
+use tui::widgets::{Block, Borders, Gauge, Paragraph, Text, Widget};
+use tui::{Frame, Terminal};
 
+use crate::app;
 use crate::app::App;
 use crate::file_ops;
+use crate::file_ops::FileKind;
+use crate::finder::Finder;
+use crate::grep;
+use crate::grep::Grep;
+use crate::jobs;
+use crate::messages::{Message, Severity};
+use crate::paste;
+use crate::preview::{Preview, PreviewKind};
+use crate::trash;
 
-pub fn draw(app: &mut App) -> Result<(), io::Error> {
-    let command_string = app.get_command_buffer_as_string();
-    let mut reset_error = false;
+//Draws `app` on any backend: the real terminal, or a test buffer
+pub fn draw<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<(), io::Error> {
+    //The line in the command box, and where the cursor goes in it while a command is typed
+    let (mut command_string, command_cursor) = command_line(app);
+
+    let highlights: Vec<Highlight> = (0..app.directory_contents.len())
+        .map(|index| Highlight {
+            marked: app.is_marked(index),
+            matched: app.is_search_match(index),
+        })
+        .collect();
+    let marked_count = highlights
+        .iter()
+        .filter(|highlight| highlight.marked)
+        .count();
+
+    //The list title shows the directory, the sort order, anything narrowing the listing and
+    //how many entries are marked
+    let mut title = format!(
+        "Contents─{}─{}",
+        file_ops::display_path(&app.current_directory),
+        app.sort.describe()
+    );
+    if let Some(filter) = &app.filter {
+        title.push_str(&format!("─filter '{}'", filter.text));
+    }
+    if !app.show_hidden {
+        title.push_str("─no dotfiles");
+    }
+    if app.respect_ignore_files {
+        title.push_str("─gitignore");
+    }
+    if app.directory_contents.len() < app.all_contents.len() {
+        title.push_str(&format!(
+            "─{}/{} shown",
+            app.directory_contents.len(),
+            app.all_contents.len()
+        ));
+    }
+    if marked_count > 0 {
+        title.push_str(&format!("─{} marked", marked_count));
+    }
+
+    if app.mode == app::Mode::Select {
+        command_string = format!("-- SELECT -- {} marked", marked_count);
+    } else if app.mode == app::Mode::Search {
+        command_string = format!("/{}", app.search_buffer);
+    }
 
     let App {
-        current_directory,
-        terminal,
         directory_contents,
         selection_index,
-        error,
+        messages,
+        paste_plan,
+        mode,
+        jobs,
+        trash_info,
+        confirmation,
+        text_view,
+        finder,
+        grep,
+        show_preview,
+        previewer,
         ..
     } = app;
 
     terminal.hide_cursor()?;
 
+    let mut cursor_position = None;
     terminal.draw(|mut f| {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+            .constraints(
+                [
+                    Constraint::Min(3),
+                    Constraint::Length(jobs_panel_height(&jobs.jobs)),
+                    Constraint::Length(3),
+                ]
+                .as_ref(),
+            )
             .split(f.size());
 
+        //With the preview on, the file list shares its space with it
+        let list_area = if *show_preview {
+            let halves = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(chunks[0]);
+
+            let selected = selection_index.and_then(|index| directory_contents.get(index));
+            draw_preview(
+                &mut f,
+                halves[1],
+                selected,
+                selected.and_then(|item| previewer.get(item.path())),
+            );
+            halves[0]
+        } else {
+            chunks[0]
+        };
+
         draw_file_list(
             &mut f,
-            chunks[0],
+            list_area,
             directory_contents,
             selection_index,
-            current_directory,
+            &highlights,
+            &title,
+            trash_info,
         );
 
-        //Error & command box drawing
-        if let Some(err) = error {
-            draw_error(&mut f, chunks[1], err);
-            reset_error = true;
-        } else {
-            draw_command_buffer(&mut f, chunks[1], command_string);
+        if !jobs.jobs.is_empty() {
+            draw_jobs(&mut f, chunks[1], &jobs.jobs);
+        }
+
+        //Message & command box drawing. What's being typed, or the visual selection's status,
+        //takes precedence over messages.
+        let prompting = matches!(
+            *mode,
+            app::Mode::Command | app::Mode::Search | app::Mode::Select
+        );
+        match messages.current() {
+            Some(message) if !prompting => draw_message(&mut f, chunks[2], message),
+            _ => {
+                let width = chunks[2].width.saturating_sub(2) as usize;
+                let (visible, column) =
+                    scroll_command_line(&command_string, command_cursor.unwrap_or(0), width);
+                draw_command_buffer(&mut f, chunks[2], visible);
+                if command_cursor.is_some() {
+                    cursor_position = Some((chunks[2].x + 1 + column as u16, chunks[2].y + 1));
+                }
+            }
+        }
+
+        if *mode == app::Mode::Conflict {
+            if let Some(plan) = paste_plan {
+                draw_conflict_dialog(&mut f, plan);
+            }
+        }
+
+        if *mode == app::Mode::View {
+            if let Some(view) = text_view {
+                draw_text_view(&mut f, view);
+            }
+        }
+
+        if *mode == app::Mode::Find {
+            if let Some(finder) = finder {
+                draw_finder(&mut f, finder);
+            }
+        }
+
+        if *mode == app::Mode::Grep {
+            if let Some(grep) = grep {
+                draw_grep(&mut f, grep);
+            }
+        }
+
+        if *mode == app::Mode::Confirm {
+            if let Some(confirmation) = confirmation {
+                draw_confirm_dialog(&mut f, &confirmation.title, &confirmation.message());
+            }
         }
     })?;
 
-    if reset_error {
-        thread::sleep(std::time::Duration::from_secs(1));
-        app.error = None;
+    if let Some((x, y)) = cursor_position {
+        terminal.set_cursor(x, y)?;
+        terminal.show_cursor()?;
     }
 
+    app.messages.drawn();
+
     Ok(())
 }
 
+//How an entry in the file list stands out, apart from being selected
+#[derive(Clone, Copy)]
+pub struct Highlight {
+    pub marked: bool,
+    pub matched: bool,
+}
+
 pub fn draw_file_list<B: Backend>(
     frame: &mut Frame<B>,
     area: Rect,
     files: &Vec<file_ops::DirectoryItem>,
     selected_file: &Option<usize>,
-    current_dir: &PathBuf,
+    highlights: &[Highlight],
+    title: &str,
+    trash_info: &HashMap<OsString, trash::TrashEntry>,
 ) {
     let mut names: Vec<Text> = Vec::new();
     let mut sizes: Vec<Text> = Vec::new();
-    let inner_rect = Rect::new(area.x + 1, area.y + 1, area.width - 1, area.height - 1); //Shrinking the area by 1 in every direction for the text columns, as border is drawn separately
+    //Shrinking the area by 1 in every direction for the text columns, as border is drawn separately
+    let inner_rect = Rect::new(
+        area.x + 1,
+        area.y + 1,
+        area.width.saturating_sub(1),
+        area.height.saturating_sub(1),
+    );
 
     //Draw the border
     Block::default()
         .borders(Borders::ALL)
-        .title(format!("Contents─{}", current_dir.to_str().unwrap()).as_ref())
+        .title(title)
         .render(frame, area);
 
-    if files.len() != 0 {
+    if !files.is_empty() {
         //Convert DirectoryItems to Text
         for file in files {
-            match file {
-                file_ops::DirectoryItem::File((path, size)) => {
-                    let split: Vec<&str> = path.split('/').collect();
-                    let string = String::from(format!("📄 {}\n", split[split.len() - 1 as usize]));
-                    names.push(Text::raw(string));
-                    sizes.push(Text::raw(format!("{}KB\n", size.to_string())));
+            let icon = match file.kind {
+                FileKind::File => "📄",
+                FileKind::Directory => "📁",
+                FileKind::Symlink { .. } => "🔗",
+                FileKind::Fifo => "📮",
+                FileKind::Socket => "🔌",
+                FileKind::BlockDevice | FileKind::CharDevice => "💾",
+            };
+
+            let string = match &file.kind {
+                FileKind::Symlink { target, .. } => {
+                    format!(
+                        "{} {} -> {}\n",
+                        icon,
+                        file_ops::display_name(file.name()),
+                        file_ops::display_path(target)
+                    )
                 }
-                file_ops::DirectoryItem::Directory(path) => {
-                    let split: Vec<&str> = path.split('/').collect();
-                    let string = String::from(format!("📁 {}\n", split[split.len() - 1 as usize]));
-                    names.push(Text::raw(string));
-                    sizes.push(Text::raw("\n"));
+                _ => format!("{} {}\n", icon, file_ops::display_name(file.name())),
+            };
+            names.push(Text::raw(string));
+
+            if file.kind == FileKind::File {
+                let kilobytes = (file.size as f64 / 1000.00).ceil() as u64;
+                sizes.push(Text::raw(format!("{}KB\n", kilobytes)));
+            } else {
+                sizes.push(Text::raw("\n"));
+            }
+        }
+
+        //In the trash, show where each entry was deleted from instead of its size
+        if !trash_info.is_empty() {
+            for (index, file) in files.iter().enumerate() {
+                if let Some(entry) = trash_info.get(file.name()) {
+                    sizes[index] = Text::raw(format!(
+                        "{}  {}\n",
+                        file_ops::display_path(&entry.original_path),
+                        entry.deletion_date.replacen('T', " ", 1)
+                    ));
                 }
             }
         }
 
-        //Highlight selected file
-        if let Some(selection_index) = selected_file {
-            //Get name of selected file
-            let selected = match &mut names[*selection_index] {
+        //Highlight marked, selected and matching files, and broken symlinks
+        for (index, name) in names.iter_mut().enumerate() {
+            let is_selected = *selected_file == Some(index);
+            let is_broken = matches!(files[index].kind, FileKind::Symlink { broken: true, .. });
+            let Highlight { marked, matched } = highlights[index];
+            if !is_selected && !marked && !matched && !is_broken {
+                continue;
+            }
+
+            let value = match name {
                 Text::Raw(value) => value,
                 _ => "",
             }
             .to_string();
 
-            //Replace name of selected file with bold name
-            names.insert(
-                *selection_index,
-                Text::styled(
-                    selected,
-                    Style::default()
-                        .modifier(Modifier::BOLD)
-                        .fg(Color::Indexed(2)),
-                ),
-            );
-            names.remove(selection_index + 1);
+            let mut style = Style::default();
+            if is_broken {
+                style = style.fg(Color::Red);
+            }
+            if matched {
+                style = style.fg(Color::Cyan).modifier(Modifier::UNDERLINED);
+            }
+            if marked {
+                style = style.fg(Color::Yellow);
+            }
+            if is_selected {
+                style = style.modifier(Modifier::BOLD);
+                if !marked && !matched && !is_broken {
+                    style = style.fg(Color::Indexed(2));
+                }
+            }
+
+            *name = Text::styled(value, style);
         }
 
-        //Figure out number of columns and their spacing
-        let columns: u16 = (names.len() as f32 / (area.height - 2) as f32).ceil() as u16;
+        //Figure out number of columns and their spacing. A terminal too short for any rows
+        //still gets one column, which just isn't visible.
+        let rows = area.height.saturating_sub(2).max(1);
+        let columns: u16 = (names.len() as f32 / rows as f32).ceil() as u16;
         let column_size: u16 = 100 / columns;
         let mut constraints: Vec<Constraint> = Vec::new();
 
@@ -132,8 +331,8 @@ pub fn draw_file_list<B: Backend>(
             .split(inner_rect);
 
         for i in 0..=columns - 1 {
-            let height: usize = (area.height - 2) as usize; // -2 to account for the border
-            let from: usize = (i as usize * height) as usize;
+            let height: usize = rows as usize;
+            let from: usize = i as usize * height;
             let mut to: usize = (i as usize * height) + (height);
 
             if to >= names.len() {
@@ -155,7 +354,7 @@ pub fn draw_file_list<B: Backend>(
                     Rect {
                         //create new Rect that doesn't overlap the border
                         height: chunks[i as usize].height,
-                        width: chunks[i as usize].width - 2,
+                        width: chunks[i as usize].width.saturating_sub(2),
                         x: chunks[i as usize].x,
                         y: chunks[i as usize].y,
                     },
@@ -164,6 +363,174 @@ pub fn draw_file_list<B: Backend>(
     }
 }
 
+//Height of the jobs panel: one line per job plus the border, or nothing when idle
+pub fn jobs_panel_height(jobs: &[jobs::Job]) -> u16 {
+    if jobs.is_empty() {
+        0
+    } else {
+        jobs.len().min(5) as u16 + 2
+    }
+}
+
+//The selected entry's preview, or a placeholder while it's being made
+pub fn draw_preview<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    item: Option<&file_ops::DirectoryItem>,
+    preview: Option<&Preview>,
+) {
+    let name = item
+        .map(|item| file_ops::display_name(item.name()))
+        .unwrap_or_default();
+    let text: Vec<Text> = match preview {
+        Some(Preview {
+            highlighted: Some(highlighted),
+            ..
+        }) => highlighted
+            .iter()
+            .flat_map(|line| {
+                line.iter()
+                    .map(|span| Text::styled(span.text.as_str(), span.style))
+                    .chain(std::iter::once(Text::raw("\n")))
+            })
+            .collect(),
+        Some(preview) => {
+            let style = if preview.kind == PreviewKind::Error {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            preview
+                .lines
+                .iter()
+                .map(|line| Text::styled(format!("{}\n", line), style))
+                .collect()
+        }
+        None if item.is_some() => vec![Text::raw("Loading…")],
+        None => Vec::new(),
+    };
+
+    let kind = match preview.map(|preview| preview.kind) {
+        Some(PreviewKind::Text) => "─text",
+        Some(PreviewKind::Binary) => "─binary",
+        Some(PreviewKind::Directory) => "─directory",
+        _ => "",
+    };
+    let title = format!("Preview─{}{}", name, kind);
+
+    Paragraph::new(text.iter())
+        .block(Block::default().title(&title).borders(Borders::ALL))
+        .render(frame, area);
+}
+
+pub fn draw_jobs<B: Backend>(frame: &mut Frame<B>, area: Rect, jobs: &[jobs::Job]) {
+    Block::default()
+        .title("Jobs")
+        .borders(Borders::ALL)
+        .render(frame, area);
+
+    for (index, job) in jobs.iter().take(5).enumerate() {
+        let row = Rect::new(
+            area.x + 1,
+            area.y + 1 + index as u16,
+            area.width.saturating_sub(2),
+            1,
+        );
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+            .split(row);
+
+        let progress = &job.progress;
+        //Shell commands can't tell how far along they are, so they show how long they've run
+        if job.kind == jobs::JobKind::Shell {
+            let seconds = job.started.elapsed().as_secs();
+            let mut status = format!(
+                "#{} {} {}  {}:{:02}",
+                job.id,
+                job.kind.name(),
+                job.description,
+                seconds / 60,
+                seconds % 60
+            );
+            if progress.is_paused() {
+                status.push_str("  [paused]");
+            }
+            let text = [Text::raw(status)];
+            Paragraph::new(text.iter()).wrap(false).render(frame, row);
+            continue;
+        }
+
+        let mut status = format!(
+            "#{} {} {}  {}/{} files",
+            job.id,
+            job.kind.name(),
+            job.description,
+            progress.files_done.load(Ordering::Relaxed),
+            progress.files_total.load(Ordering::Relaxed),
+        );
+
+        let bytes_total = progress.bytes_total.load(Ordering::Relaxed);
+        if bytes_total > 0 {
+            status.push_str(&format!(
+                "  {}/{}",
+                file_ops::format_size(progress.bytes_done.load(Ordering::Relaxed)),
+                file_ops::format_size(bytes_total)
+            ));
+        }
+
+        if progress.is_paused() {
+            status.push_str("  [paused]");
+        } else if let Some(eta) = job.eta() {
+            let seconds = eta.as_secs();
+            status.push_str(&format!("  ETA {}:{:02}", seconds / 60, seconds % 60));
+        }
+
+        let text = [Text::raw(status)];
+        Paragraph::new(text.iter())
+            .wrap(false)
+            .render(frame, columns[0]);
+
+        let label = format!("{}%", job.percent());
+        Gauge::default()
+            .style(Style::default().fg(Color::Indexed(2)))
+            .percent(job.percent())
+            .label(&label)
+            .render(frame, columns[1]);
+    }
+}
+
+//The command line as drawn, with the cursor's position in characters. While searching the
+//history it shows the search and the command found.
+fn command_line(app: &App) -> (String, Option<usize>) {
+    if app.mode != app::Mode::Command {
+        return (String::new(), None);
+    }
+
+    match &app.history_search {
+        Some(search) => {
+            let prompt = format!("(reverse-i-search)`{}", search.query);
+            let cursor = prompt.chars().count();
+            let found = search.matched(&app.history).unwrap_or("");
+            (format!("{}': {}", prompt, found), Some(cursor))
+        }
+        None => (
+            format!(":{}", app.command_line.text()),
+            Some(app.command_line.cursor + 1),
+        ),
+    }
+}
+
+//The part of a line that fits in `width` columns with the cursor in view, and the cursor's
+//column in that part
+fn scroll_command_line(line: &str, cursor: usize, width: usize) -> (String, usize) {
+    let skip = (cursor + 1).saturating_sub(width);
+    (
+        line.chars().skip(skip).take(width).collect(),
+        cursor.saturating_sub(skip),
+    )
+}
+
 pub fn draw_command_buffer<B: Backend>(frame: &mut Frame<B>, area: Rect, command_string: String) {
     let text: Vec<Text> = vec![Text::raw(command_string)];
 
@@ -172,18 +539,298 @@ pub fn draw_command_buffer<B: Backend>(frame: &mut Frame<B>, area: Rect, command
         .render(frame, area);
 }
 
-pub fn draw_error<B: Backend>(frame: &mut Frame<B>, area: Rect, error: &String) {
+pub fn draw_message<B: Backend>(frame: &mut Frame<B>, area: Rect, message: &Message) {
+    let colour = match message.severity {
+        Severity::Info => Color::Cyan,
+        Severity::Warning => Color::Yellow,
+        Severity::Error => Color::Red,
+    };
     let text: Vec<Text> = vec![Text::styled(
-        error.to_string(),
-        Style::default().fg(Color::Red),
+        message.text.clone(),
+        Style::default().fg(colour),
+    )];
+
+    Paragraph::new(text.iter())
+        .block(
+            Block::default()
+                .title(message.severity.name())
+                .borders(Borders::ALL)
+                .style(Style::default().fg(colour)),
+        )
+        .render(frame, area);
+}
+
+pub fn draw_conflict_dialog<B: Backend>(frame: &mut Frame<B>, plan: &paste::PastePlan) {
+    let index = match plan.next_conflict() {
+        Some(index) => index,
+        None => return,
+    };
+    let item = &plan.items[index];
+    let conflict_number = plan.items[..=index]
+        .iter()
+        .filter(|item| item.conflict)
+        .count();
+
+    let name = file_ops::display_file_name(&item.destination);
+    let describe = |path: &Path| match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => String::from("directory"),
+        Ok(metadata) => file_ops::format_size(metadata.len()),
+        Err(_) => String::from("unreadable"),
+    };
+    let (source_age, destination_age) = if paste::is_newer(&item.source, &item.destination) {
+        (", newer", "")
+    } else if paste::is_newer(&item.destination, &item.source) {
+        ("", ", newer")
+    } else {
+        ("", "")
+    };
+
+    let text = [
+        Text::styled(
+            format!("'{}' already exists\n\n", name),
+            Style::default().modifier(Modifier::BOLD),
+        ),
+        Text::raw(format!(
+            "Pasting:  {}{}\n",
+            describe(&item.source),
+            source_age
+        )),
+        Text::raw(format!(
+            "Existing: {}{}\n\n",
+            describe(&item.destination),
+            destination_age
+        )),
+        Text::raw("[o]verwrite  [s]kip  [r]ename  overwrite if [n]ewer\n"),
+        Text::raw("Shift applies to all remaining conflicts, Esc cancels"),
+    ];
+
+    let area = centered_rect(64, 9, frame.size());
+    Clear.render(frame, area);
+    Paragraph::new(text.iter())
+        .block(
+            Block::default()
+                .title(
+                    format!(
+                        "Paste conflict {} of {}",
+                        conflict_number,
+                        plan.conflict_count()
+                    )
+                    .as_ref(),
+                )
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .wrap(true)
+        .render(frame, area);
+}
+
+pub fn draw_text_view<B: Backend>(frame: &mut Frame<B>, view: &app::TextView) {
+    let size = frame.size();
+    let area = centered_rect(
+        size.width.saturating_sub(8),
+        size.height.saturating_sub(4),
+        size,
+    );
+
+    let text: Vec<Text> = view
+        .lines
+        .iter()
+        .skip(view.scroll)
+        .map(|line| Text::raw(format!("{}\n", line)))
+        .collect();
+
+    Clear.render(frame, area);
+    Paragraph::new(text.iter())
+        .block(
+            Block::default()
+                .title(format!("{}─j/k to scroll, Esc to close", view.title).as_ref())
+                .borders(Borders::ALL),
+        )
+        .render(frame, area);
+}
+
+//The fuzzy finder: the query on the first line and the best matches below it, with the
+//characters that matched picked out
+pub fn draw_finder<B: Backend>(frame: &mut Frame<B>, finder: &Finder) {
+    let size = frame.size();
+    let area = centered_rect(
+        size.width.saturating_sub(8),
+        size.height.saturating_sub(4),
+        size,
+    );
+    //Rows left for matches inside the border and under the query
+    let rows = area.height.saturating_sub(3) as usize;
+    let scroll = (finder.selected + 1).saturating_sub(rows);
+
+    let mut text = vec![Text::styled(
+        format!("> {}\n", finder.query),
+        Style::default().modifier(Modifier::BOLD),
     )];
+    for (index, found) in finder.matches.iter().enumerate().skip(scroll).take(rows) {
+        let base = if index == finder.selected {
+            Style::default()
+                .fg(Color::Indexed(2))
+                .modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+
+        //Runs of matched and unmatched characters become separately styled pieces
+        let display = &finder.candidates[found.candidate].display;
+        let mut piece = String::new();
+        let mut piece_matched = false;
+        for (position, character) in display.chars().enumerate() {
+            let matched = found.positions.contains(&position);
+            if matched != piece_matched && !piece.is_empty() {
+                text.push(finder_piece(
+                    std::mem::take(&mut piece),
+                    piece_matched,
+                    base,
+                ));
+            }
+            piece_matched = matched;
+            piece.push(character);
+        }
+        piece.push('\n');
+        text.push(finder_piece(piece, piece_matched, base));
+    }
+
+    let title = format!(
+        "Find─{}/{} files{}─Enter to go to, Esc to close",
+        finder.matches.len(),
+        finder.candidates.len(),
+        if finder.walking { ", searching…" } else { "" }
+    );
+
+    Clear.render(frame, area);
+    Paragraph::new(text.iter())
+        .block(Block::default().title(&title).borders(Borders::ALL))
+        .render(frame, area);
+}
+
+fn finder_piece(piece: String, matched: bool, base: Style) -> Text<'static> {
+    if matched {
+        Text::styled(piece, base.fg(Color::Yellow))
+    } else {
+        Text::styled(piece, base)
+    }
+}
+
+//Results of :grep, one matching line per row as path:line: text
+pub fn draw_grep<B: Backend>(frame: &mut Frame<B>, grep: &Grep) {
+    let size = frame.size();
+    let area = centered_rect(
+        size.width.saturating_sub(8),
+        size.height.saturating_sub(4),
+        size,
+    );
+    let rows = area.height.saturating_sub(2) as usize;
+    let scroll = (grep.selected + 1).saturating_sub(rows);
+
+    let mut text = Vec::new();
+    for (index, found) in grep.matches.iter().enumerate().skip(scroll).take(rows) {
+        let base = if index == grep.selected {
+            Style::default().modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
 
+        text.push(Text::styled(
+            file_ops::display_path(&found.path),
+            base.fg(Color::Cyan),
+        ));
+        text.push(Text::styled(
+            format!(":{}:", found.line_number),
+            base.fg(Color::Yellow),
+        ));
+        let line_style = if index == grep.selected {
+            base.fg(Color::Indexed(2))
+        } else {
+            base
+        };
+        text.push(Text::styled(format!(" {}\n", found.line), line_style));
+    }
+
+    let files = grep.files_searched.load(Ordering::Relaxed);
+    let status = if grep.is_truncated() {
+        format!(
+            ", stopped after {}",
+            file_ops::format_count(grep::MAX_MATCHES as u64)
+        )
+    } else if grep.searching {
+        String::from(", searching…")
+    } else {
+        String::new()
+    };
+    let title = format!(
+        "grep '{}'─{} in {} searched{}─Enter to go to, e to edit, Esc to close",
+        grep.pattern,
+        file_ops::format_quantity(grep.matches.len() as u64, "match", "matches"),
+        file_ops::format_quantity(files as u64, "file", "files"),
+        status
+    );
+
+    Clear.render(frame, area);
+    Paragraph::new(text.iter())
+        .block(Block::default().title(&title).borders(Borders::ALL))
+        .render(frame, area);
+}
+
+//Yes/no question drawn over everything else. `message` may span several lines.
+pub fn draw_confirm_dialog<B: Backend>(frame: &mut Frame<B>, title: &str, message: &str) {
+    let text = [
+        Text::styled(
+            format!("{}\n\n", message),
+            Style::default().modifier(Modifier::BOLD),
+        ),
+        Text::raw("[y]es  [n]o"),
+    ];
+
+    let width = message
+        .lines()
+        .map(|line| line.chars().count() as u16)
+        .max()
+        .unwrap_or(0)
+        .max(30)
+        + 4;
+    let height = message.lines().count() as u16 + 4;
+
+    let area = centered_rect(width, height, frame.size());
+    Clear.render(frame, area);
     Paragraph::new(text.iter())
         .block(
             Block::default()
-                .title("Error")
+                .title(title)
                 .borders(Borders::ALL)
                 .style(Style::default().fg(Color::Red)),
         )
+        .alignment(Alignment::Center)
+        .wrap(true)
         .render(frame, area);
 }
+
+//Rect of the given size in the middle of `area`, shrunk to fit if needed
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+//Blanks out an area so a popup can be drawn over the file list
+pub struct Clear;
+
+impl Widget for Clear {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        for x in area.left()..area.right() {
+            for y in area.top()..area.bottom() {
+                buf.get_mut(x, y).reset();
+            }
+        }
+    }
+}
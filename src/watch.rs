@@ -0,0 +1,44 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+//Directories are watched with inotify on Linux. Elsewhere nothing is watched, and the app reads
+//the listed directory again now and then instead.
+#[cfg(target_os = "linux")]
+pub use crate::inotify::Watcher;
+
+//Only made by the inotify watcher
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub enum ChangeKind {
+    //The entry was created, deleted or changed; what's there now has to be looked at
+    Changed,
+    //The entry was renamed from this name in the same directory
+    Renamed(OsString),
+    //Too much happened to keep track of, or the directory itself went away
+    Rescan,
+}
+
+pub struct Change {
+    pub directory: PathBuf,
+    //Empty for Rescan
+    pub name: OsString,
+    pub kind: ChangeKind,
+}
+
+#[cfg(not(target_os = "linux"))]
+pub struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    pub fn new(_waker: crate::events::Waker) -> std::io::Result<Watcher> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "directories can only be watched on Linux",
+        ))
+    }
+
+    pub fn watch_listed(&mut self, _directory: &std::path::Path) -> bool {
+        false
+    }
+
+    pub fn watch_previewed(&mut self, _directory: Option<&std::path::Path>) {}
+}